@@ -1,4 +1,6 @@
-use crate::{keycodes::StrKeyCode, utils::ss_idx, BG_COLOR, CONSOLE_BG, CONSOLE_NO_BG};
+use crate::{
+    camera::Camera, keycodes::StrKeyCode, utils::ss_idx, BG_COLOR, CONSOLE_BG, CONSOLE_NO_BG,
+};
 use bracket_lib::prelude::*;
 use glsp::prelude::*;
 
@@ -59,6 +61,7 @@ pub fn bind_utils() -> GResult<()> {
     glsp::bind_rfn("cls", &cls)?;
     glsp::bind_rfn("set", &set_char_glsp)?;
     glsp::bind_rfn("set_bg", &set_bg_glsp)?;
+    glsp::bind_rfn("highlight", &highlight_glsp)?;
     glsp::bind_rfn("exit", &exit)?;
     glsp::bind_rfn("ss-idx", &ss_idx)?;
     glsp::bind_rfn("console:log", &|v: Val| console::log(v.to_string()))?;
@@ -112,7 +115,9 @@ pub fn set_console(id: usize) {
         .push(GlspCommand::SetConsole { id });
 }
 
-/// Draws a char on screen (glsp fn)
+/// Draws a char on screen (glsp fn).
+/// On the map consoles, x and y are world coordinates, converted through the camera.
+/// On the other consoles (UI, mouse), they're screen coordinates
 pub fn set_char_glsp(
     x: i32,
     y: i32,
@@ -146,7 +151,14 @@ pub fn set_char_glsp(
             panic!("invalid glyph")
         }
     };
-    set_char(x, y, glyph, fg, bg, console);
+    if console == CONSOLE_BG || console == CONSOLE_NO_BG {
+        // Chars outside of the viewport are skipped
+        if let Some(pos) = Camera::borrow().world_to_screen(x, y) {
+            set_char(pos.x, pos.y, glyph, fg, bg, console);
+        }
+    } else {
+        set_char(x, y, glyph, fg, bg, console);
+    }
 }
 
 pub fn set_bg_glsp(x: i32, y: i32, bg: &RGB) {
@@ -155,6 +167,15 @@ pub fn set_bg_glsp(x: i32, y: i32, bg: &RGB) {
         .push(GlspCommand::SetBgColor { x, y, bg: *bg });
 }
 
+/// Changes the background of a map tile, keeping its glyph (glsp fn).
+/// x and y are world coordinates, tiles outside of the viewport are skipped
+pub fn highlight_glsp(x: i32, y: i32, bg: &RGB) {
+    if let Some(pos) = Camera::borrow().world_to_screen(x, y) {
+        set_console(CONSOLE_BG);
+        set_bg_glsp(pos.x, pos.y, bg);
+    }
+}
+
 /// Draws a char on screen (general fn)
 pub fn set_char(x: i32, y: i32, glyph: FontCharType, fg: &RGB, bg: &RGB, console: usize) {
     let command = GlspCommand::SetChar {
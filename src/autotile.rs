@@ -0,0 +1,135 @@
+use crate::map::Map;
+
+// Neighbours bitmask
+const NORTH: u8 = 1;
+const SOUTH: u8 = 2;
+const WEST: u8 = 4;
+const EAST: u8 = 8;
+
+/// Walls are non-walkable tiles, but closed doors are part of the room outlines
+fn is_wall(map: &Map, idx: usize) -> bool {
+    !map.tiles[idx].walkable && map.tiles[idx].opens_to.is_none()
+}
+
+/// Floors are walkable tiles and doors
+fn is_floor(map: &Map, idx: usize) -> bool {
+    map.tiles[idx].walkable || map.tiles[idx].opens_to.is_some()
+}
+
+/// An edge is a wall that touches a floor, including diagonally.
+/// Walls inside the rock are not edges
+fn is_edge(map: &Map, x: i32, y: i32) -> bool {
+    let idx = map.xy_idx(x, y);
+    is_wall(map, idx) && map.neighbours_8(idx).any(|n| is_floor(map, n))
+}
+
+/// Returns the 4-neighbours bitmask of an edge: each bit is set
+/// when the neighbour in that direction is also an edge (or a door)
+pub fn wall_mask(map: &Map, x: i32, y: i32) -> u8 {
+    let mut mask = 0;
+    for (dx, dy, bit) in [(0, -1, NORTH), (0, 1, SOUTH), (-1, 0, WEST), (1, 0, EAST)] {
+        let (nx, ny) = (x + dx, y + dy);
+        if let Some(idx) = map.try_xy_idx(nx, ny) {
+            if is_edge(map, nx, ny) || map.tiles[idx].opens_to.is_some() {
+                mask |= bit;
+            }
+        }
+    }
+    mask
+}
+
+/// Sets the line glyph of each edge, according to its neighbours.
+/// To be called once the map is generated
+pub fn autotile(map: &mut Map) {
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx].autotile = if is_edge(map, x, y) {
+                map.tiles[idx].autotile_glyph(wall_mask(map, x, y))
+            } else {
+                None
+            };
+        }
+    }
+}
+
+/// Line glyphs are only drawn once a floor next to the wall has been revealed.
+/// Until then, the wall is drawn with its solid glyph
+pub fn touches_revealed_floor(map: &Map, idx: usize) -> bool {
+    map.neighbours_8(idx)
+        .any(|n| map.revealed_tiles[n] && is_floor(map, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_utils::map_from;
+
+    /// A map from rows of `#` (walls) and `.` (floors), autotiled
+    fn autotiled_map(rows: &[&str]) -> Map {
+        let mut map = map_from(rows);
+        autotile(&mut map);
+        map
+    }
+
+    #[test]
+    fn test_room_corners_and_sides() {
+        let map = autotiled_map(&["#####", "#...#", "#...#", "#...#", "#####"]);
+        assert_eq!(wall_mask(&map, 0, 0), SOUTH | EAST);
+        assert_eq!(wall_mask(&map, 4, 0), SOUTH | WEST);
+        assert_eq!(wall_mask(&map, 0, 4), NORTH | EAST);
+        assert_eq!(wall_mask(&map, 4, 4), NORTH | WEST);
+        assert_eq!(wall_mask(&map, 2, 0), WEST | EAST);
+        assert_eq!(wall_mask(&map, 0, 2), NORTH | SOUTH);
+    }
+
+    #[test]
+    fn test_autotile_glyphs() {
+        let map = autotiled_map(&["#####", "#...#", "#...#", "#...#", "#####"]);
+        let top_left = map.xy_idx(0, 0);
+        assert_eq!(
+            map.tiles[top_left].autotile,
+            Some(100 + (SOUTH | EAST) as u16)
+        );
+        let floor = map.xy_idx(2, 2);
+        assert_eq!(map.tiles[floor].autotile, None);
+    }
+
+    #[test]
+    fn test_rock_is_not_an_edge() {
+        let map = autotiled_map(&[
+            "#######", "#######", "##...##", "##...##", "##...##", "#######", "#######",
+        ]);
+        // outer ring doesn't touch any floor
+        assert!(!is_edge(&map, 0, 0));
+        assert!(!is_edge(&map, 3, 0));
+        assert_eq!(map.tiles[map.xy_idx(3, 0)].autotile, None);
+        // so it doesn't connect to the room outline
+        assert_eq!(wall_mask(&map, 1, 1), SOUTH | EAST);
+        assert_eq!(wall_mask(&map, 3, 1), WEST | EAST);
+    }
+
+    #[test]
+    fn test_pillar() {
+        let map = autotiled_map(&[".....", ".....", "..#..", ".....", "....."]);
+        assert!(is_edge(&map, 2, 2));
+        assert_eq!(wall_mask(&map, 2, 2), 0);
+    }
+
+    #[test]
+    fn test_corridor_junction() {
+        let map = autotiled_map(&["#####", "#...#", "#....", "#...#", "#####"]);
+        // the wall above the corridor exit ends there
+        assert_eq!(wall_mask(&map, 4, 1), NORTH);
+        assert_eq!(wall_mask(&map, 4, 3), SOUTH);
+    }
+
+    #[test]
+    fn test_revealed_floor() {
+        let mut map = autotiled_map(&["###", "#.#", "###"]);
+        assert!(!touches_revealed_floor(&map, map.xy_idx(0, 0)));
+        let floor = map.xy_idx(1, 1);
+        map.revealed_tiles[floor] = true;
+        assert!(touches_revealed_floor(&map, map.xy_idx(0, 0)));
+    }
+}
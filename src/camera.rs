@@ -0,0 +1,72 @@
+use bracket_lib::prelude::*;
+use glsp::prelude::*;
+
+use crate::map::Map;
+
+/// Maps the world coordinates to the screen coordinates.
+/// The viewport is the area of the screen where the map is drawn
+pub struct Camera {
+    /// World position of the top-left corner of the viewport
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl RGlobal for Camera {}
+
+impl Camera {
+    pub fn new(width: i32, height: i32) -> Self {
+        Camera {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    pub fn bind() -> GResult<()> {
+        glsp::bind_rfn("camera:follow!", &|map: &Map, x: i32, y: i32| {
+            Camera::borrow_mut().follow(map, x, y)
+        })?;
+        glsp::bind_rfn("camera:world->screen", &|x: i32, y: i32| {
+            Camera::borrow().world_to_screen(x, y)
+        })?;
+        glsp::bind_rfn("camera:screen->world", &|x: i32, y: i32| {
+            Camera::borrow().screen_to_world(x, y)
+        })?;
+        glsp::bind_rfn("camera:visible?", &|x: i32, y: i32| {
+            Camera::borrow().world_to_screen(x, y).is_some()
+        })?;
+        Ok(())
+    }
+
+    /// Centers the viewport on a position, without going past the map borders.
+    /// Maps smaller than the viewport are centered
+    pub fn follow(&mut self, map: &Map, x: i32, y: i32) {
+        self.x = Camera::clamp_axis(x - self.width / 2, self.width, map.width);
+        self.y = Camera::clamp_axis(y - self.height / 2, self.height, map.height);
+    }
+
+    fn clamp_axis(start: i32, viewport: i32, map: i32) -> i32 {
+        if map <= viewport {
+            (map - viewport) / 2
+        } else {
+            start.max(0).min(map - viewport)
+        }
+    }
+
+    /// Returns the screen position of a world position, if it's inside the viewport
+    pub fn world_to_screen(&self, x: i32, y: i32) -> Option<Point> {
+        let (sx, sy) = (x - self.x, y - self.y);
+        if sx < 0 || sy < 0 || sx >= self.width || sy >= self.height {
+            None
+        } else {
+            Some(Point::new(sx, sy))
+        }
+    }
+
+    pub fn screen_to_world(&self, x: i32, y: i32) -> Point {
+        Point::new(x + self.x, y + self.y)
+    }
+}
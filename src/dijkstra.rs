@@ -0,0 +1,251 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use glsp::prelude::*;
+
+use crate::map::{Map, DIAGONAL_COST};
+
+/// Multi-source Dijkstra map: each tile holds its distance to the closest source.
+/// It's computed once and shared by all the entities that need it
+/// (e.g. every monster chasing the player)
+pub struct DijkstraMap {
+    width: i32,
+    height: i32,
+    values: Vec<f32>,
+    /// Tiles that could be traversed when the map was built
+    passable: Vec<bool>,
+    costs: Vec<f32>,
+    max_depth: f32,
+}
+
+/// Open node, ordered by lowest value first
+struct Node {
+    idx: usize,
+    value: f32,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .value
+            .partial_cmp(&self.value)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl DijkstraMap {
+    pub fn bind() -> GResult<()> {
+        glsp::RClassBuilder::<DijkstraMap>::new()
+            .met("value-at", &DijkstraMap::value_at)
+            .met("lowest-neighbour", &DijkstraMap::lowest_neighbour)
+            .met("highest-neighbour", &DijkstraMap::highest_neighbour)
+            .met("invert", &|dm: &DijkstraMap, factor: Option<f32>| {
+                dm.invert(factor.unwrap_or(-1.2))
+            })
+            .build();
+        Ok(())
+    }
+
+    /// Builds a map from a list of sources.
+    /// If `honour_blocked` is set, tiles blocked by entities can't be traversed
+    pub fn new(map: &Map, sources: &[usize], honour_blocked: bool, max_depth: f32) -> Self {
+        let passable = (0..map.tiles.len())
+            .map(|idx| map.is_passable(idx, honour_blocked))
+            .collect();
+        let costs = map.tiles.iter().map(|tile| tile.cost).collect();
+        let mut dm = DijkstraMap {
+            width: map.width,
+            height: map.height,
+            values: vec![],
+            passable,
+            costs,
+            max_depth,
+        };
+        let seeds = sources.iter().map(|&idx| (idx, 0.0)).collect::<Vec<_>>();
+        dm.values = dm.scan(&seeds);
+        dm
+    }
+
+    /// Runs the Dijkstra algorithm from seeds with initial values
+    fn scan(&self, seeds: &[(usize, f32)]) -> Vec<f32> {
+        let mut values = vec![f32::MAX; self.passable.len()];
+        let mut open = BinaryHeap::new();
+        for &(idx, value) in seeds.iter() {
+            if idx < values.len() && value < values[idx] {
+                values[idx] = value;
+                open.push(Node { idx, value });
+            }
+        }
+
+        while let Some(Node { idx, value }) = open.pop() {
+            if value > values[idx] || value >= self.max_depth {
+                continue;
+            }
+            for (neighbour, step) in self.exits(idx) {
+                let new_value = value + step * self.costs[neighbour];
+                if new_value < values[neighbour] {
+                    values[neighbour] = new_value;
+                    open.push(Node {
+                        idx: neighbour,
+                        value: new_value,
+                    });
+                }
+            }
+        }
+        values
+    }
+
+    /// Passable neighbours of a tile, with the step cost
+    fn exits(&self, idx: usize) -> Vec<(usize, f32)> {
+        let x = idx as i32 % self.width;
+        let y = idx as i32 / self.width;
+        let mut exits = vec![];
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (nx, ny) = (x + dx, y + dy);
+                if (dx, dy) == (0, 0) || nx < 0 || ny < 0 || nx >= self.width || ny >= self.height {
+                    continue;
+                }
+                let neighbour = (ny * self.width + nx) as usize;
+                if self.passable[neighbour] {
+                    let step = if dx != 0 && dy != 0 {
+                        DIAGONAL_COST
+                    } else {
+                        1.0
+                    };
+                    exits.push((neighbour, step));
+                }
+            }
+        }
+        exits
+    }
+
+    /// Returns the distance to the closest source, or nil if the tile is unreachable
+    fn value_at(&self, idx: usize) -> GResult<Option<f32>> {
+        match self.values.get(idx) {
+            Some(&value) if value < f32::MAX => Ok(Some(value)),
+            Some(_) => Ok(None),
+            None => bail!("Index {} is out of the Dijkstra map", idx),
+        }
+    }
+
+    /// Returns the neighbour with the lowest value, if it's lower than the tile's own value.
+    /// Following the lowest neighbours leads to the closest source
+    fn lowest_neighbour(&self, idx: usize) -> GResult<Option<usize>> {
+        self.best_neighbour(idx, |candidate, best| candidate < best)
+    }
+
+    /// Returns the neighbour with the highest value, if it's higher than the tile's own value
+    fn highest_neighbour(&self, idx: usize) -> GResult<Option<usize>> {
+        self.best_neighbour(idx, |candidate, best| {
+            candidate > best && candidate < f32::MAX
+        })
+    }
+
+    fn best_neighbour(&self, idx: usize, better: fn(f32, f32) -> bool) -> GResult<Option<usize>> {
+        if idx >= self.values.len() {
+            bail!("Index {} is out of the Dijkstra map", idx);
+        }
+        let mut best = (None, self.values[idx]);
+        for (neighbour, _) in self.exits(idx) {
+            let value = self.values[neighbour];
+            if better(value, best.1) {
+                best = (Some(neighbour), value);
+            }
+        }
+        Ok(best.0)
+    }
+
+    /// Returns a flee map: the values are multiplied by a negative factor, then rescanned.
+    /// Following its lowest neighbours leads away from the sources,
+    /// while avoiding dead ends
+    pub fn invert(&self, factor: f32) -> DijkstraMap {
+        let seeds = self
+            .values
+            .iter()
+            .enumerate()
+            .filter(|(_, &value)| value < f32::MAX)
+            .map(|(idx, &value)| (idx, value * factor))
+            .collect::<Vec<_>>();
+        let mut dm = DijkstraMap {
+            width: self.width,
+            height: self.height,
+            values: vec![],
+            passable: self.passable.to_vec(),
+            costs: self.costs.to_vec(),
+            max_depth: f32::MAX,
+        };
+        dm.values = dm.scan(&seeds);
+        dm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_utils::map_from;
+
+    #[test]
+    fn test_multiple_sources() {
+        let map = map_from(&[".........."; 5]);
+        let sources = [map.xy_idx(0, 2), map.xy_idx(9, 2)];
+        let dm = DijkstraMap::new(&map, &sources, false, f32::MAX);
+        assert_eq!(dm.value_at(sources[0]).unwrap(), Some(0.0));
+        assert_eq!(dm.value_at(map.xy_idx(2, 2)).unwrap(), Some(2.0));
+        assert_eq!(dm.value_at(map.xy_idx(7, 2)).unwrap(), Some(2.0));
+        assert_eq!(dm.value_at(map.xy_idx(4, 2)).unwrap(), Some(4.0));
+        assert_eq!(dm.value_at(map.xy_idx(1, 1)).unwrap(), Some(DIAGONAL_COST));
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let map = map_from(&[".........."; 5]);
+        let dm = DijkstraMap::new(&map, &[map.xy_idx(0, 2)], false, f32::MAX);
+        let idx = map.xy_idx(5, 2);
+        let lowest = dm.lowest_neighbour(idx).unwrap().unwrap();
+        assert_eq!(lowest, map.xy_idx(4, 2));
+        let highest = dm.highest_neighbour(idx).unwrap().unwrap();
+        assert_eq!(map.idx_xy(highest as i32).x, 6);
+        // the source has no lower neighbour
+        assert_eq!(dm.lowest_neighbour(map.xy_idx(0, 2)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invert() {
+        let map = map_from(&[".........."; 5]);
+        let flee = DijkstraMap::new(&map, &[map.xy_idx(0, 2)], false, f32::MAX).invert(-1.2);
+        // the flee map leads away from the source
+        let next = flee.lowest_neighbour(map.xy_idx(5, 2)).unwrap().unwrap();
+        assert_eq!(map.idx_xy(next as i32).x, 6);
+        assert!(
+            flee.value_at(map.xy_idx(9, 2)).unwrap() < flee.value_at(map.xy_idx(1, 2)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_honour_blocked() {
+        let mut map = map_from(&["#####", "#...#", "#####"]);
+        let blocked = map.xy_idx(2, 1);
+        map.blocked_tiles[blocked] = true;
+        let sources = [map.xy_idx(1, 1)];
+        let end = map.xy_idx(3, 1);
+
+        let dm = DijkstraMap::new(&map, &sources, false, f32::MAX);
+        assert_eq!(dm.value_at(end).unwrap(), Some(2.0));
+        let dm = DijkstraMap::new(&map, &sources, true, f32::MAX);
+        assert_eq!(dm.value_at(end).unwrap(), None);
+    }
+}
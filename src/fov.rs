@@ -0,0 +1,404 @@
+use bracket_lib::prelude::*;
+use glsp::prelude::*;
+
+use crate::map::Map;
+use crate::tile::{TileType, TILE_REGISTRY};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FovAlgorithm {
+    /// Symmetric shadowcasting: if a tile can see another, the reverse is true
+    Shadowcast,
+    /// bracket-lib's `field_of_view`
+    Bracket,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RadiusShape {
+    Circle,
+    Square,
+    Diamond,
+}
+
+impl RadiusShape {
+    pub fn contains(&self, dx: i32, dy: i32, range: i32) -> bool {
+        match self {
+            // the extra `range` rounds the circle, avoiding single tiles on its sides
+            RadiusShape::Circle => dx * dx + dy * dy <= range * range + range,
+            RadiusShape::Square => dx.abs().max(dy.abs()) <= range,
+            RadiusShape::Diamond => dx.abs() + dy.abs() <= range,
+        }
+    }
+}
+
+/// How an entity sees, stored in its `Viewshed`
+#[derive(Clone, PartialEq, Debug)]
+pub struct FovOptions {
+    pub algorithm: FovAlgorithm,
+    pub shape: RadiusShape,
+    /// Opaque tile types that don't block this viewer's sight
+    pub see_through: Vec<TileType>,
+    /// Whether the walls that stop the sight are visible themselves
+    pub light_walls: bool,
+    /// Only lit tiles are visible (see lighting.rs)
+    pub needs_light: bool,
+}
+
+impl Default for FovOptions {
+    fn default() -> Self {
+        FovOptions {
+            algorithm: FovAlgorithm::Shadowcast,
+            shape: RadiusShape::Circle,
+            see_through: vec![],
+            light_walls: true,
+            needs_light: false,
+        }
+    }
+}
+
+impl FovOptions {
+    pub fn bind() -> GResult<()> {
+        glsp::RClassBuilder::<FovOptions>::new()
+            .prop_get("light-walls", &|opts: &FovOptions| opts.light_walls)
+            .prop_get("needs-light", &|opts: &FovOptions| opts.needs_light)
+            .build();
+        glsp::bind_rfn("fov:options", &FovOptions::from_glsp)?;
+        Ok(())
+    }
+
+    /// `(fov:options algorithm shape see-through light-walls needs-light)`, all optional:
+    /// algorithm is `shadowcast` or `bracket`, shape is `circle`, `square` or `diamond`,
+    /// see-through is a list of tile names
+    fn from_glsp(
+        algorithm: Option<Sym>,
+        shape: Option<Sym>,
+        see_through: Option<Vec<Sym>>,
+        light_walls: Option<bool>,
+        needs_light: Option<bool>,
+    ) -> GResult<FovOptions> {
+        let mut opts = FovOptions::default();
+        if let Some(algorithm) = algorithm {
+            opts.algorithm = match &*algorithm.name() {
+                "shadowcast" => FovAlgorithm::Shadowcast,
+                "bracket" => FovAlgorithm::Bracket,
+                other => bail!("Unknown fov algorithm {}", other),
+            };
+        }
+        if let Some(shape) = shape {
+            opts.shape = match &*shape.name() {
+                "circle" => RadiusShape::Circle,
+                "square" => RadiusShape::Square,
+                "diamond" => RadiusShape::Diamond,
+                other => bail!("Unknown fov shape {}", other),
+            };
+        }
+        let registry = TILE_REGISTRY.lock().unwrap();
+        for name in see_through.unwrap_or_default() {
+            match registry.find(&name.name()) {
+                Some(tile_type) => opts.see_through.push(tile_type),
+                None => bail!("Tile type {} is not registered", name),
+            }
+        }
+        opts.light_walls = light_walls.unwrap_or(true);
+        opts.needs_light = needs_light.unwrap_or(false);
+        Ok(opts)
+    }
+
+    fn blocks_sight(&self, map: &Map, idx: usize) -> bool {
+        let tile = &map.tiles[idx];
+        tile.opaque && !self.see_through.contains(&tile.tile_type)
+    }
+}
+
+/// Returns the indexes of the tiles visible from a position.
+/// Only tiles inside the map are returned, and the origin is always visible,
+/// even in the dark
+pub fn field_of_view(map: &Map, origin: Point, range: i32, opts: &FovOptions) -> Vec<usize> {
+    let origin_idx = match map.try_xy_idx(origin.x, origin.y) {
+        Some(idx) => idx,
+        None => return vec![],
+    };
+    let mut visible = vec![false; map.tiles.len()];
+    visible[origin_idx] = true;
+
+    match opts.algorithm {
+        FovAlgorithm::Shadowcast => {
+            let mut fov = Shadowcast {
+                map,
+                opts,
+                origin,
+                range,
+                visible: &mut visible,
+            };
+            for quadrant in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                let row = Row {
+                    depth: 1,
+                    start: Slope::new(-1, 1),
+                    end: Slope::new(1, 1),
+                };
+                fov.scan(quadrant, row);
+            }
+        }
+        FovAlgorithm::Bracket => {
+            let sight = SightMap { map, opts };
+            for pos in bracket_lib::prelude::field_of_view(origin, range, &sight) {
+                let (dx, dy) = (pos.x - origin.x, pos.y - origin.y);
+                if let Some(idx) = map.try_xy_idx(pos.x, pos.y) {
+                    let lit = opts.light_walls || !opts.blocks_sight(map, idx);
+                    if lit && opts.shape.contains(dx, dy, range) {
+                        visible[idx] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    visible
+        .iter()
+        .enumerate()
+        .filter(|(idx, &visible)| {
+            visible && (!opts.needs_light || *idx == origin_idx || map.is_lit(*idx))
+        })
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Exact slope of a row boundary, as `num / den` with `den > 0`
+#[derive(Clone, Copy)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+impl Slope {
+    fn new(num: i32, den: i32) -> Self {
+        Slope { num, den }
+    }
+
+    /// Slope of the left edge of a tile
+    fn of_tile(depth: i32, col: i32) -> Self {
+        Slope::new(2 * col - 1, 2 * depth)
+    }
+}
+
+/// A row of tiles at a given distance from the origin, between two slopes
+#[derive(Clone, Copy)]
+struct Row {
+    depth: i32,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    /// Columns of the row, from the start slope (rounded up) to the end slope (rounded down)
+    fn columns(&self) -> std::ops::RangeInclusive<i32> {
+        let (start, end) = (self.start, self.end);
+        // floor(depth * start + 1/2)
+        let min = (2 * self.depth * start.num + start.den).div_euclid(2 * start.den);
+        // ceil(depth * end - 1/2)
+        let max = -(end.den - 2 * self.depth * end.num).div_euclid(2 * end.den);
+        min..=max
+    }
+
+    /// The center of the tile is inside the row's sector
+    fn is_symmetric(&self, col: i32) -> bool {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+
+    fn next(&self) -> Row {
+        Row {
+            depth: self.depth + 1,
+            ..*self
+        }
+    }
+}
+
+/// Symmetric shadowcasting, scanning each quadrant row by row
+/// (see https://www.albertford.com/shadowcasting/)
+struct Shadowcast<'a> {
+    map: &'a Map,
+    opts: &'a FovOptions,
+    origin: Point,
+    range: i32,
+    visible: &'a mut [bool],
+}
+
+impl Shadowcast<'_> {
+    /// Map position of a tile in a quadrant, facing the `(qx, qy)` direction
+    fn transform(&self, (qx, qy): (i32, i32), depth: i32, col: i32) -> (i32, i32) {
+        if qx == 0 {
+            (self.origin.x + col, self.origin.y + qy * depth)
+        } else {
+            (self.origin.x + qx * depth, self.origin.y + col)
+        }
+    }
+
+    /// Tiles outside the map block the sight
+    fn blocks(&self, pos: (i32, i32)) -> bool {
+        match self.map.try_xy_idx(pos.0, pos.1) {
+            Some(idx) => self.opts.blocks_sight(self.map, idx),
+            None => true,
+        }
+    }
+
+    fn reveal(&mut self, pos: (i32, i32)) {
+        let (dx, dy) = (pos.0 - self.origin.x, pos.1 - self.origin.y);
+        if !self.opts.shape.contains(dx, dy, self.range) {
+            return;
+        }
+        if let Some(idx) = self.map.try_xy_idx(pos.0, pos.1) {
+            if self.opts.light_walls || !self.opts.blocks_sight(self.map, idx) {
+                self.visible[idx] = true;
+            }
+        }
+    }
+
+    fn scan(&mut self, quadrant: (i32, i32), mut row: Row) {
+        if row.depth > self.range {
+            return;
+        }
+        let mut prev_blocks = None;
+        for col in row.columns() {
+            let pos = self.transform(quadrant, row.depth, col);
+            let blocks = self.blocks(pos);
+            if blocks || row.is_symmetric(col) {
+                self.reveal(pos);
+            }
+            if prev_blocks == Some(true) && !blocks {
+                row.start = Slope::of_tile(row.depth, col);
+            }
+            if prev_blocks == Some(false) && blocks {
+                let mut next = row.next();
+                next.end = Slope::of_tile(row.depth, col);
+                self.scan(quadrant, next);
+            }
+            prev_blocks = Some(blocks);
+        }
+        if prev_blocks == Some(false) {
+            self.scan(quadrant, row.next());
+        }
+    }
+}
+
+/// Map seen through a viewer's eyes, for bracket-lib's algorithm
+struct SightMap<'a> {
+    map: &'a Map,
+    opts: &'a FovOptions,
+}
+
+impl BaseMap for SightMap<'_> {
+    fn is_opaque(&self, idx: usize) -> bool {
+        self.opts.blocks_sight(self.map, idx)
+    }
+}
+
+impl Algorithm2D for SightMap<'_> {
+    fn dimensions(&self) -> Point {
+        self.map.dimensions()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_utils::map_from;
+    use crate::tile::WALL;
+
+    /// A map from rows of `#` (walls) and `.` (floors), with `@` marking the origin
+    fn map_and_origin(rows: &[&str]) -> (Map, Point) {
+        let origin = rows
+            .iter()
+            .enumerate()
+            .find_map(|(y, row)| row.find('@').map(|x| Point::new(x as i32, y as i32)))
+            .unwrap_or_else(|| Point::new(0, 0));
+        (map_from(rows), origin)
+    }
+
+    fn visible(map: &Map, origin: Point, range: i32, opts: &FovOptions, x: i32, y: i32) -> bool {
+        field_of_view(map, origin, range, opts).contains(&map.xy_idx(x, y))
+    }
+
+    #[test]
+    fn test_room_is_visible() {
+        let (map, origin) = map_and_origin(&["#####", "#...#", "#.@.#", "#...#", "#####"]);
+        let fov = field_of_view(&map, origin, 8, &FovOptions::default());
+        // the whole room, walls included
+        assert_eq!(fov.len(), 25);
+    }
+
+    #[test]
+    fn test_walls_block_sight() {
+        let (map, origin) = map_and_origin(&["#######", "#@.#..#", "#######"]);
+        let opts = FovOptions::default();
+        assert!(visible(&map, origin, 8, &opts, 3, 1));
+        assert!(!visible(&map, origin, 8, &opts, 4, 1));
+    }
+
+    #[test]
+    fn test_light_walls() {
+        let (map, origin) = map_and_origin(&["#####", "#.@.#", "#####"]);
+        let opts = FovOptions {
+            light_walls: false,
+            ..FovOptions::default()
+        };
+        let fov = field_of_view(&map, origin, 8, &opts);
+        assert_eq!(fov.len(), 3);
+    }
+
+    #[test]
+    fn test_see_through() {
+        let (map, origin) = map_and_origin(&["#######", "#@.#..#", "#######"]);
+        let opts = FovOptions {
+            see_through: vec![TileType::named(WALL).unwrap()],
+            ..FovOptions::default()
+        };
+        assert!(visible(&map, origin, 8, &opts, 4, 1));
+    }
+
+    #[test]
+    fn test_shapes() {
+        let (map, origin) = map_and_origin(&[
+            ".......", ".......", ".......", "...@...", ".......", ".......", ".......",
+        ]);
+        let square = FovOptions {
+            shape: RadiusShape::Square,
+            ..FovOptions::default()
+        };
+        let diamond = FovOptions {
+            shape: RadiusShape::Diamond,
+            ..FovOptions::default()
+        };
+        assert!(visible(&map, origin, 2, &square, 1, 1));
+        assert!(!visible(&map, origin, 2, &diamond, 1, 1));
+        assert!(visible(&map, origin, 2, &diamond, 3, 1));
+        assert!(!visible(&map, origin, 2, &FovOptions::default(), 1, 1));
+    }
+
+    #[test]
+    fn test_symmetry() {
+        let (map, _) = map_and_origin(&[
+            "##########",
+            "#........#",
+            "#..#..#..#",
+            "#....#...#",
+            "##########",
+        ]);
+        let opts = FovOptions::default();
+        let floors = (0..map.tiles.len())
+            .filter(|&idx| map.tiles[idx].walkable)
+            .collect::<Vec<_>>();
+        for &a in floors.iter() {
+            let from_a = field_of_view(&map, map.idx_xy(a as i32), 20, &opts);
+            for &b in floors.iter() {
+                let from_b = field_of_view(&map, map.idx_xy(b as i32), 20, &opts);
+                assert_eq!(from_a.contains(&b), from_b.contains(&a));
+            }
+        }
+    }
+
+    #[test]
+    fn test_origin_outside() {
+        let (map, _) = map_and_origin(&["...", "...", "..."]);
+        assert!(field_of_view(&map, Point::new(-1, 0), 8, &FovOptions::default()).is_empty());
+    }
+}
@@ -1,32 +1,213 @@
+use std::collections::VecDeque;
+
 use glsp::prelude::*;
 
+/// Entries kept by default, the oldest ones are dropped
+const DEFAULT_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogCategory {
+    Combat,
+    Item,
+    System,
+}
+
+impl LogCategory {
+    fn from_sym(sym: Sym) -> GResult<Self> {
+        match &*sym.name() {
+            "combat" => Ok(LogCategory::Combat),
+            "item" => Ok(LogCategory::Item),
+            "system" => Ok(LogCategory::System),
+            name => bail!("Unknown log category {}", name),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            LogCategory::Combat => "combat",
+            LogCategory::Item => "item",
+            LogCategory::System => "system",
+        }
+    }
+}
+
+pub struct LogEntry {
+    /// Can contain color markup (see text.rs)
+    pub text: String,
+    pub category: LogCategory,
+    /// Turn of the last occurrence
+    pub turn: i32,
+    /// Occurrences of the same message in a row
+    pub count: u32,
+}
+
+impl LogEntry {
+    /// The text, with its number of occurrences: "Orc hits you x3"
+    pub fn display(&self) -> String {
+        if self.count > 1 {
+            format!("{} x{}", self.text, self.count)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
 pub struct GameLog {
-    pub entries: Vec<String>,
+    pub entries: VecDeque<LogEntry>,
+    capacity: usize,
+    /// Stamped on the new entries
+    turn: i32,
+    /// Bumped on each change, for the views caching the entries
+    revision: i32,
 }
 
 impl GameLog {
     pub fn new() -> Self {
-        GameLog { entries: vec![] }
+        GameLog {
+            entries: VecDeque::new(),
+            capacity: DEFAULT_CAPACITY,
+            turn: 0,
+            revision: 0,
+        }
     }
-    pub fn add<T: ToString>(&mut self, entry: T) {
-        self.entries.push(entry.to_string());
+
+    /// Adds an entry, or merges it with the previous one if it's the same message
+    pub fn add<T: ToString>(&mut self, entry: T, category: LogCategory) {
+        let text = entry.to_string();
+        self.revision = self.revision.wrapping_add(1);
+        if let Some(last) = self.entries.back_mut() {
+            if last.text == text && last.category == category {
+                last.count += 1;
+                last.turn = self.turn;
+                return;
+            }
+        }
+        self.entries.push_back(LogEntry {
+            text,
+            category,
+            turn: self.turn,
+            count: 1,
+        });
+        self.truncate();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    fn truncate(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Entries of the given categories, or all of them, oldest first
+    fn filtered(&self, categories: &[LogCategory]) -> impl Iterator<Item = &LogEntry> {
+        let categories = categories.to_vec();
+        self.entries
+            .iter()
+            .filter(move |e| categories.is_empty() || categories.contains(&e.category))
     }
-    pub fn get_messages(&self) -> Vec<String> {
-        self.entries.to_vec()
+
+    fn parse_categories(categories: Rest<Sym>) -> GResult<Vec<LogCategory>> {
+        categories
+            .iter()
+            .map(|&sym| LogCategory::from_sym(sym))
+            .collect()
     }
 
     pub fn rglobal_add(entry: &String) {
-        GameLog::borrow_mut().add(entry);
+        GameLog::borrow_mut().add(entry, LogCategory::System);
     }
 
     pub fn bind() -> GResult<()> {
         glsp::add_rglobal(GameLog::new());
-        glsp::bind_rfn("log:add", &GameLog::add::<Val>)?;
-        glsp::bind_rfn("log:get", &GameLog::get_messages)?;
+        glsp::bind_rfn("log:add", &|entry: Val,
+                                    category: Option<Sym>|
+         -> GResult<()> {
+            let category = match category {
+                Some(sym) => LogCategory::from_sym(sym)?,
+                None => LogCategory::System,
+            };
+            GameLog::borrow_mut().add(entry, category);
+            Ok(())
+        })?;
+        // messages, e.g. (log:get) or (log:get 'combat 'item)
+        glsp::bind_rfn(
+            "log:get",
+            &|categories: Rest<Sym>| -> GResult<Vec<String>> {
+                let categories = GameLog::parse_categories(categories)?;
+                Ok(GameLog::borrow()
+                    .filtered(&categories)
+                    .map(LogEntry::display)
+                    .collect())
+            },
+        )?;
+        // (turn category message) triples
+        glsp::bind_rfn(
+            "log:entries",
+            &|categories: Rest<Sym>| -> GResult<Vec<(i32, Sym, String)>> {
+                let categories = GameLog::parse_categories(categories)?;
+                GameLog::borrow()
+                    .filtered(&categories)
+                    .map(|e| Ok((e.turn, glsp::sym(e.category.name())?, e.display())))
+                    .collect()
+            },
+        )?;
+        glsp::bind_rfn("log:clear", &|| GameLog::borrow_mut().clear())?;
+        glsp::bind_rfn("log:revision", &|| GameLog::borrow().revision)?;
+        glsp::bind_rfn("log:set-turn!", &|turn: i32| {
+            GameLog::borrow_mut().turn = turn
+        })?;
+        glsp::bind_rfn("log:set-capacity!", &|capacity: usize| {
+            let mut log = GameLog::borrow_mut();
+            log.capacity = capacity;
+            log.truncate();
+        })?;
         Ok(())
     }
 }
 
 impl RGlobal for GameLog {}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_duplicates() {
+        let mut log = GameLog::new();
+        log.add("Orc hits you", LogCategory::Combat);
+        log.turn = 2;
+        log.add("Orc hits you", LogCategory::Combat);
+        log.add("Orc hits you", LogCategory::Combat);
+        log.add("You open the door", LogCategory::System);
+        assert_eq!(log.entries.len(), 2);
+        assert_eq!(log.entries[0].display(), "Orc hits you x3");
+        assert_eq!(log.entries[0].turn, 2);
+        assert_eq!(log.entries[1].display(), "You open the door");
+    }
 
+    #[test]
+    fn test_ring_buffer() {
+        let mut log = GameLog::new();
+        log.capacity = 3;
+        for i in 0..5 {
+            log.add(i, LogCategory::System);
+        }
+        let texts = log.entries.iter().map(|e| e.display()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_filter_categories() {
+        let mut log = GameLog::new();
+        log.add("hit", LogCategory::Combat);
+        log.add("potion", LogCategory::Item);
+        log.add("door", LogCategory::System);
+        let combat = log.filtered(&[LogCategory::Combat]).count();
+        assert_eq!(combat, 1);
+        assert_eq!(log.filtered(&[]).count(), 3);
+    }
+}
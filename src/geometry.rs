@@ -0,0 +1,97 @@
+use bracket_lib::prelude::*;
+
+/// Slack on the cone angle, so tiles exactly on its border are included
+const CONE_EPSILON: f32 = 1e-4;
+
+/// Bresenham line from `start` to `end`, both included.
+/// The line from B to A is not always the reverse of the line from A to B
+pub fn bresenham(start: Point, end: Point) -> Vec<Point> {
+    let (dx, dy) = ((end.x - start.x).abs(), -(end.y - start.y).abs());
+    let (sx, sy) = ((end.x - start.x).signum(), (end.y - start.y).signum());
+    let mut err = dx + dy;
+    let mut pos = start;
+    let mut points = vec![pos];
+    while pos != end {
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            pos.x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            pos.y += sy;
+        }
+        points.push(pos);
+    }
+    points
+}
+
+/// Whether `pos` is inside the cone starting at `origin`, pointing towards `target`,
+/// `angle` degrees wide. The origin itself is not part of the cone
+pub fn in_cone(origin: Point, target: Point, pos: Point, angle: f32) -> bool {
+    let dir = ((target.x - origin.x) as f32, (target.y - origin.y) as f32);
+    let to_pos = ((pos.x - origin.x) as f32, (pos.y - origin.y) as f32);
+    let (dir_len, pos_len) = (dir.0.hypot(dir.1), to_pos.0.hypot(to_pos.1));
+    if dir_len == 0.0 || pos_len == 0.0 {
+        return false;
+    }
+    let cos = (dir.0 * to_pos.0 + dir.1 * to_pos.1) / (dir_len * pos_len);
+    cos >= (angle.to_radians() / 2.0).cos() - CONE_EPSILON
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bresenham_straight() {
+        let line = bresenham(Point::new(0, 0), Point::new(3, 0));
+        assert_eq!(
+            line,
+            vec![
+                Point::new(0, 0),
+                Point::new(1, 0),
+                Point::new(2, 0),
+                Point::new(3, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bresenham_diagonal() {
+        let line = bresenham(Point::new(2, 2), Point::new(-1, -1));
+        assert_eq!(line.len(), 4);
+        assert_eq!(line[1], Point::new(1, 1));
+        assert_eq!(line[3], Point::new(-1, -1));
+    }
+
+    #[test]
+    fn test_bresenham_steep() {
+        let line = bresenham(Point::new(0, 0), Point::new(2, 5));
+        assert_eq!(line.len(), 6);
+        assert_eq!(line.last(), Some(&Point::new(2, 5)));
+        // one step at a time
+        for pair in line.windows(2) {
+            assert!((pair[1].x - pair[0].x).abs() <= 1);
+            assert_eq!(pair[1].y - pair[0].y, 1);
+        }
+    }
+
+    #[test]
+    fn test_bresenham_single_point() {
+        let line = bresenham(Point::new(4, 4), Point::new(4, 4));
+        assert_eq!(line, vec![Point::new(4, 4)]);
+    }
+
+    #[test]
+    fn test_cone() {
+        let origin = Point::new(0, 0);
+        let target = Point::new(5, 0);
+        assert!(in_cone(origin, target, Point::new(3, 0), 90.0));
+        // exactly on the border
+        assert!(in_cone(origin, target, Point::new(3, 3), 90.0));
+        assert!(!in_cone(origin, target, Point::new(3, 4), 90.0));
+        assert!(!in_cone(origin, target, Point::new(-1, 0), 90.0));
+        assert!(!in_cone(origin, target, origin, 90.0));
+    }
+}
@@ -4,7 +4,11 @@ use glsp::{compile, prelude::*};
 
 use crate::{
     api::{self, KeyPressed},
+    camera::Camera,
+    dijkstra::DijkstraMap,
+    fov::FovOptions,
     gamelog::GameLog,
+    scheduler::Scheduler,
     utils::str_to_hashed,
     *,
 };
@@ -74,6 +78,7 @@ impl GlspInterpreter {
             // internals
             glsp::add_rglobal(api::CommandQueue::new());
             glsp::add_rglobal(KeyPressed::new());
+            glsp::add_rglobal(Camera::new(VIEWPORT_WIDTH, VIEWPORT_HEIGHT));
 
             // constants & globals
             glsp::bind_global(":pressed-key", "")?;
@@ -81,6 +86,9 @@ impl GlspInterpreter {
             glsp::bind_global(":height", HEIGHT)?;
             glsp::bind_global(":bg-color", RGB::named(BG_COLOR))?;
             glsp::bind_global(":mouse", (0, 0))?;
+            glsp::bind_global(":mouse-world", (0, 0))?;
+            glsp::bind_global(":mouse-click", false)?;
+            glsp::bind_global(":mouse-wheel", 0)?;
             glsp::bind_global(":fps", 0)?;
 
             // log
@@ -88,11 +96,19 @@ impl GlspInterpreter {
 
             // api
             Map::bind_map()?;
+            Camera::bind()?;
+            DijkstraMap::bind()?;
+            FovOptions::bind()?;
+            map_builders::bind_map_builders()?;
+            tile::TileRegistry::bind()?;
             World::bind_world()?;
             api::bind_utils()?;
             api::bind_geometry()?;
             gui::bind_gui()?;
             GameLog::bind()?;
+            morgue::bind_morgue()?;
+            Scheduler::bind()?;
+            text::bind_text()?;
 
             // colors
             glsp::bind_rfn("Color", &api::rgb_color)?;
@@ -153,6 +169,18 @@ impl GlspInterpreter {
                 KeyPressed::borrow_mut().0.take();
             }
             glsp::set_global(":mouse", ctx.mouse_pos())?;
+            let (mouse_x, mouse_y) = ctx.mouse_pos();
+            let mouse_world = Camera::borrow().screen_to_world(mouse_x, mouse_y);
+            glsp::set_global(":mouse-world", (mouse_world.x, mouse_world.y))?;
+            glsp::set_global(":mouse-click", ctx.left_click)?;
+            // wheel notches of this frame, > 0 when scrolling up
+            let mut wheel = 0;
+            INPUT.lock().for_each_message(|event| {
+                if let BEvent::MouseWheel { delta } = event {
+                    wheel += delta.y;
+                }
+            });
+            glsp::set_global(":mouse-wheel", wheel)?;
             glsp::set_global(":fps", ctx.fps)?;
 
             // Call the `(defn main:update)` function
@@ -1,8 +1,9 @@
 use bracket_lib::prelude::*;
-use glsp::{GResult, RGlobal, Val};
+use glsp::{bail, GResult, RGlobal, Sym, Val};
 
 use crate::{
     api::{set_char, set_console, CommandQueue, GlspCommand},
+    text::{parse_markup, wrap_spans, Span},
     utils::ss_idx,
     BG_COLOR, CONSOLE_BG, CONSOLE_UI,
 };
@@ -11,6 +12,7 @@ pub fn bind_gui() -> GResult<()> {
     glsp::bind_rfn("draw-box", &draw_box)?;
     glsp::bind_rfn("draw-h-bar", &progress_bar_h)?;
     glsp::bind_rfn("print", &print)?;
+    glsp::bind_rfn("print-text", &print_text)?;
     Ok(())
 }
 
@@ -55,6 +57,7 @@ fn draw_box(x: i32, y: i32, w: i32, h: i32, fg: Option<&RGB>, bg: Option<&RGB>)
     }
 }
 
+/// Prints a text, which can contain color markup (see text.rs)
 fn print(x: i32, y: i32, output: Val, fg: Option<&RGB>, bg: Option<&RGB>) {
     let fg = &match fg {
         Some(fg) => *fg,
@@ -64,15 +67,71 @@ fn print(x: i32, y: i32, output: Val, fg: Option<&RGB>, bg: Option<&RGB>) {
         Some(bg) => *bg,
         None => RGB::named(BG_COLOR),
     };
-    let command = GlspCommand::Print {
-        x,
-        y,
-        output: output.to_string(),
-        fg: *fg,
-        bg: *bg,
+    print_spans(x, y, &parse_markup(&output.to_string()), fg, bg);
+}
+
+/// Prints a text in a `w` x `h` rect, wrapped at its width and clipped to its height.
+/// `align` is 'left (the default), 'center or 'right.
+/// Returns the number of lines printed
+#[allow(clippy::too_many_arguments)]
+fn print_text(
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    output: Val,
+    align: Option<Sym>,
+    fg: Option<&RGB>,
+    bg: Option<&RGB>,
+) -> GResult<i32> {
+    let align = match align {
+        Some(sym) => sym.name().to_string(),
+        None => "left".to_string(),
+    };
+    if !["left", "center", "right"].contains(&align.as_str()) {
+        bail!("Unknown alignment {}", align);
+    }
+    let fg = &match fg {
+        Some(fg) => *fg,
+        None => RGB::named(WHITE),
     };
+    let bg = &match bg {
+        Some(bg) => *bg,
+        None => RGB::named(BG_COLOR),
+    };
+    let lines = wrap_spans(&parse_markup(&output.to_string()), w.max(1) as usize);
+    let mut printed = 0;
+    for (line, dy) in lines.iter().zip(0..h) {
+        let width = line.iter().map(Span::width).sum::<usize>() as i32;
+        let dx = match align.as_str() {
+            "center" => (w - width) / 2,
+            "right" => w - width,
+            _ => 0,
+        };
+        print_spans(x + dx, y + dy, line, fg, bg);
+        printed += 1;
+    }
+    Ok(printed)
+}
+
+/// Prints a line of spans: the texts with `GlspCommand::Print`, the glyphs with `set_char`
+fn print_spans(x: i32, y: i32, spans: &[Span], fg: &RGB, bg: &RGB) {
     set_console(CONSOLE_UI);
-    CommandQueue::borrow_mut().0.push(command);
+    let mut x = x;
+    for span in spans {
+        let color = span.color.unwrap_or(*fg);
+        match span.glyph {
+            Some(glyph) => set_char(x, y, glyph, &color, bg, CONSOLE_UI),
+            None => CommandQueue::borrow_mut().0.push(GlspCommand::Print {
+                x,
+                y,
+                output: span.text.clone(),
+                fg: color,
+                bg: *bg,
+            }),
+        }
+        x += span.width() as i32;
+    }
 }
 
 fn progress_bar_h(x: i32, y: i32, w: i32, completed: i32, max: i32, fg: &RGB, bg: &RGB) {
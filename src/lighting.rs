@@ -0,0 +1,73 @@
+use bracket_lib::prelude::*;
+
+use crate::fov::{self, FovOptions};
+use crate::map::Map;
+
+/// Light level under which a tile is too dark to be seen
+const LIT_THRESHOLD: f32 = 0.25;
+
+/// Adds the light of a source to the map's light buffer.
+/// The light is stopped by opaque tiles, and fades with the distance:
+/// its intensity is `(1 - distance / (radius + 1)) ^ falloff`
+pub fn cast_light(map: &mut Map, origin: Point, radius: i32, color: RGB, falloff: f32) {
+    let lit = fov::field_of_view(map, origin, radius, &FovOptions::default());
+    for idx in lit {
+        let pos = map.idx_xy(idx as i32);
+        let distance = DistanceAlg::Pythagoras.distance2d(origin, pos);
+        let intensity = (1.0 - distance / (radius as f32 + 1.0))
+            .max(0.0)
+            .powf(falloff);
+        map.light[idx] = map.light[idx] + color * intensity;
+    }
+}
+
+/// Multiplies a color by a light, each channel of the light being capped to 1
+pub fn tint(color: RGB, light: RGB) -> RGB {
+    RGB::from_f32(
+        color.r * light.r.min(1.0),
+        color.g * light.g.min(1.0),
+        color.b * light.b.min(1.0),
+    )
+}
+
+/// A tile is lit when one of the light channels is bright enough
+pub fn is_lit(light: RGB) -> bool {
+    light.r.max(light.g).max(light.b) >= LIT_THRESHOLD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_utils::map_from;
+
+    #[test]
+    fn test_falloff() {
+        let mut map = map_from(&[".........."; 5]);
+        let white = RGB::from_f32(1.0, 1.0, 1.0);
+        cast_light(&mut map, Point::new(2, 2), 3, white, 1.0);
+        assert_eq!(map.light[map.xy_idx(2, 2)].r, 1.0);
+        assert!((map.light[map.xy_idx(4, 2)].r - 0.5).abs() < 1e-6);
+        assert_eq!(map.light[map.xy_idx(7, 2)].r, 0.0);
+
+        // a faster falloff, added to the light already there
+        cast_light(&mut map, Point::new(2, 2), 3, white, 2.0);
+        assert!((map.light[map.xy_idx(4, 2)].r - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_walls_stop_the_light() {
+        let mut map = map_from(&["...#......"; 5]);
+        let white = RGB::from_f32(1.0, 1.0, 1.0);
+        cast_light(&mut map, Point::new(1, 2), 5, white, 1.0);
+        assert!(is_lit(map.light[map.xy_idx(2, 2)]));
+        assert!(!is_lit(map.light[map.xy_idx(5, 2)]));
+    }
+
+    #[test]
+    fn test_lit_threshold() {
+        assert!(is_lit(RGB::from_f32(LIT_THRESHOLD, 0.0, 0.0)));
+        assert!(is_lit(RGB::from_f32(0.0, 0.0, 0.6)));
+        assert!(!is_lit(RGB::from_f32(0.2, 0.2, 0.2)));
+        assert!(!is_lit(RGB::from_f32(0.0, 0.0, 0.0)));
+    }
+}
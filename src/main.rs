@@ -3,12 +3,22 @@
 // bracket_lib::prelude::add_wasm_support!();
 
 mod api;
+mod autotile;
+mod camera;
+mod dijkstra;
 mod ecs;
+mod fov;
 mod gamelog;
+mod geometry;
 mod glsp_interpreter;
 mod gui;
 mod keycodes;
+mod lighting;
 mod map;
+mod map_builders;
+mod morgue;
+mod scheduler;
+mod text;
 mod tile;
 mod utils;
 
@@ -34,6 +44,10 @@ const CONSOLE_NO_BG: usize = 1; // Transparent background
 const CONSOLE_UI: usize = 2;
 const CONSOLE_MOUSE: usize = 3;
 
+// The map is drawn above the UI panel
+const VIEWPORT_WIDTH: i32 = WIDTH;
+const VIEWPORT_HEIGHT: i32 = HEIGHT - 7;
+
 struct State {
     interpreter: GlspInterpreter,
 }
@@ -67,6 +81,8 @@ fn main() -> BError {
         // Options
         // .with_automatic_console_resize(true)
         .with_vsync(false)
+        // for the mouse wheel events
+        .with_advanced_input(true)
         .build()?;
 
     let interpreter = GlspInterpreter::new();
@@ -1,11 +1,28 @@
-use std::cmp::{max, min};
+use std::{
+    cmp::{max, min},
+    collections::HashMap,
+};
 
 use bracket_lib::prelude::*;
 use glsp::prelude::*;
 
 use crate::api::*;
+use crate::autotile;
+use crate::camera::Camera;
+use crate::dijkstra::DijkstraMap;
 use crate::ecs::Entity;
-use crate::tile::{Tile, *};
+use crate::fov::{self, FovOptions, RadiusShape};
+use crate::geometry;
+use crate::lighting;
+use crate::tile::*;
+
+/// Cost multiplier of a diagonal step
+pub const DIAGONAL_COST: f32 = 1.45;
+/// Largest width or height of a map
+pub const MAX_MAP_SIZE: i32 = 1000;
+
+/// Cached paths, keyed by start, end, map revision and occupied cost
+type PathKey = (usize, usize, u64, Option<u32>);
 
 pub struct Map {
     pub tiles: Vec<Tile>,
@@ -16,6 +33,23 @@ pub struct Map {
     pub visible_tiles: Vec<bool>,
     pub blocked_tiles: Vec<bool>,
     pub tile_content: Vec<Vec<Entity>>,
+    pub start: Option<Point>,
+    pub exit: Option<Point>,
+    pub spawn_regions: Vec<Vec<usize>>,
+    /// Snapshots of `tiles` taken during the generation, for debugging purposes
+    pub history: Vec<Vec<Tile>>,
+    pub record_history: bool,
+    /// Incremented whenever the tiles change, and when the blocked tiles are indexed
+    pub revision: u64,
+    /// When set, tiles blocked by entities can be traversed at this extra cost
+    occupied_cost: Option<f32>,
+    path_cache: HashMap<PathKey, (bool, Vec<usize>)>,
+    /// Light cast on each tile by the light sources, on top of the ambient light
+    pub light: Vec<RGB>,
+    /// Light buffer before the last `clear-light!`, to detect the changes
+    previous_light: Vec<RGB>,
+    /// White by default: everything is lit
+    pub ambient_light: RGB,
 }
 
 impl Map {
@@ -23,45 +57,98 @@ impl Map {
         glsp::RClassBuilder::<Map>::new()
             .prop_get("width", &Map::get_width)
             .prop_get("height", &Map::get_height)
+            .prop_get("start", &|map: &Map| map.start)
+            .prop_get("exit", &|map: &Map| map.exit)
+            .prop_get("spawn-regions", &|map: &Map| map.spawn_regions.to_vec())
+            .prop_get("snapshots", &|map: &Map| map.history.len())
+            .prop_get("revision", &|map: &Map| map.revision)
             .met("init", &Map::new)
             // Coords / index
-            .met("xy-idx", &Map::xy_idx)
-            .met("idx-xy", &Map::idx_xy)
+            .met("xy-idx", &Map::xy_idx_glsp)
+            .met("idx-xy", &|map: &Map, idx: usize| -> GResult<Point> {
+                map.check_idx(idx)?;
+                Ok(map.idx_xy(idx as i32))
+            })
+            .met("in-bounds?", &Map::in_bounds)
+            .met("try-xy-idx", &Map::try_xy_idx)
+            .met("neighbours-4", &|map: &Map, idx: usize| {
+                map.neighbours_glsp(idx, false)
+            })
+            .met("neighbours-8", &|map: &Map, idx: usize| {
+                map.neighbours_glsp(idx, true)
+            })
             // Rooms / corridors
             // .met("get-room", &Map::get_room)
             .met("get-rooms", &Map::get_rooms)
-            .met("apply-room", &Map::apply_room)
+            .met("apply-room", &|map: &mut Map, room: &Rect| -> GResult<()> {
+                map.check_rect(room)?;
+                map.apply_room(room)
+            })
             .met("add-room", &Map::add_room)
             .met("apply-horizontal-tunnel", &Map::apply_horizontal_tunnel)
             .met("apply-vertical-tunnel", &Map::apply_vertical_tunnel)
-            .met("walkable?", &|map: &Map, idx: usize| {
-                !map.blocked_tiles[idx]
+            .met("walkable?", &|map: &Map, idx: usize| -> GResult<bool> {
+                map.check_idx(idx)?;
+                Ok(!map.blocked_tiles[idx])
             })
+            // Doors
+            .met("door-closed?", &|map: &Map, idx: usize| -> GResult<bool> {
+                map.check_idx(idx)?;
+                Ok(map.tiles[idx].opens_to.is_some())
+            })
+            .met("open-door!", &|map: &mut Map, idx: usize| -> GResult<()> {
+                map.check_idx(idx)?;
+                map.open_door(idx);
+                Ok(())
+            })
+            // Tiles
+            .met("tile-name", &|map: &Map, idx: usize| -> GResult<String> {
+                map.check_idx(idx)?;
+                Ok(map.tiles[idx].name())
+            })
+            .met("set-tile!", &Map::set_tile)
             // Fov / Pathing
             .met("fov", &Map::field_of_view_glsp)
-            .met("a*", &|map: &mut Map, start: usize, end: usize| {
-                a_star_search(start, end, map)
-            })
+            .met("a*", &Map::a_star)
+            .met("dijkstra", &Map::dijkstra_glsp)
+            .met("unrevealed-tiles", &Map::unrevealed_tiles)
+            // Targeting
+            .met("line-of-fire", &Map::line_of_fire)
+            .met("tiles-in-radius", &Map::tiles_in_radius)
+            .met("tiles-in-cone", &Map::tiles_in_cone)
+            .met("visible-from?", &Map::is_visible_from)
             // Population
             .met("clear-indexed-entities!", &Map::clear_content_index)
             .met("index-entity!", &Map::index_entity)
-            .met("entities-at", &|map: &Map, idx: usize| {
-                map.tile_content[idx].to_vec()
-            })
+            .met("entities-at", &Map::entities_at)
             // Blocked tiles
             .met("populate-blocked!", &Map::populate_blocked_tiles)
-            .met("block-tile!", &|map: &mut Map, idx: usize| {
-                map.blocked_tiles[idx] = true
-            })
+            .met("block-tile!", &Map::block_tile)
+            .met("unblock-tile!", &Map::unblock_tile)
             // Visibility
+            .met("ascii", &Map::ascii_snapshot)
             .met("reveal-tile!", &Map::add_tile_to_revealed)
             .met("show-tile!", &Map::add_tile_to_visible)
-            .met("visible?", &|map: &Map, idx: usize| -> bool {
-                map.visible_tiles[idx]
+            .met("visible?", &|map: &Map, idx: usize| -> GResult<bool> {
+                map.check_idx(idx)?;
+                Ok(map.visible_tiles[idx])
             })
             .met("clear-visible-tiles!", &|map: &mut Map| {
                 map.visible_tiles.iter_mut().for_each(|t| *t = false)
             })
+            // Lighting
+            .met("clear-light!", &Map::clear_light)
+            .met("add-light!", &Map::add_light)
+            .met("light-changed?", &|map: &Map| {
+                map.light != map.previous_light
+            })
+            .met("set-ambient-light!", &|map: &mut Map, color: &RGB| {
+                map.ambient_light = *color
+            })
+            .met("lit?", &|map: &Map, idx: usize| -> GResult<bool> {
+                map.check_idx(idx)?;
+                Ok(map.is_lit(idx))
+            })
             .build();
         glsp::bind_rfn("Map", &Map::new)?;
 
@@ -75,13 +162,23 @@ impl Map {
         Ok(())
     }
 
-    pub fn new(width: i32, height: i32) -> Self {
+    /// Fails if a dimension is not positive or above `MAX_MAP_SIZE`
+    pub fn new(width: i32, height: i32) -> GResult<Self> {
+        if width <= 0 || height <= 0 || width > MAX_MAP_SIZE || height > MAX_MAP_SIZE {
+            bail!(
+                "Invalid map size {}x{}, expected 1 to {} tiles per side",
+                width,
+                height,
+                MAX_MAP_SIZE
+            );
+        }
         let size = (width * height) as usize;
+        let wall = TileType::named(WALL)?;
         let mut tiles = vec![];
         for _ in 0..size {
-            tiles.push(Tile::wall());
+            tiles.push(Tile::new(wall));
         }
-        Map {
+        Ok(Map {
             tiles,
             rooms: vec![],
             width,
@@ -90,7 +187,18 @@ impl Map {
             visible_tiles: vec![false; size],
             blocked_tiles: vec![false; size],
             tile_content: vec![Vec::new(); size],
-        }
+            start: None,
+            exit: None,
+            spawn_regions: vec![],
+            history: vec![],
+            record_history: false,
+            revision: 0,
+            occupied_cost: None,
+            path_cache: HashMap::new(),
+            light: vec![RGB::named(BLACK); size],
+            previous_light: vec![RGB::named(BLACK); size],
+            ambient_light: RGB::named(WHITE),
+        })
     }
 
     fn get_width(&self) -> i32 {
@@ -105,73 +213,475 @@ impl Map {
     fn get_rooms(&self) -> Vec<Rect> {
         self.rooms.to_vec()
     }
-    fn add_room(&mut self, room: &Rect) {
+    pub fn add_room(&mut self, room: &Rect) {
         self.rooms.push(*room)
     }
 
-    fn xy_idx(&self, x: i32, y: i32) -> usize {
+    /// Unchecked: the coordinates must be inside the map (see `try_xy_idx`)
+    pub fn xy_idx(&self, x: i32, y: i32) -> usize {
         (y * self.width + x) as usize
     }
 
-    fn idx_xy(&self, idx: i32) -> Point {
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
+    }
+
+    /// Returns the index of a position, or None if it's outside the map
+    pub fn try_xy_idx(&self, x: i32, y: i32) -> Option<usize> {
+        if self.in_bounds(x, y) {
+            Some(self.xy_idx(x, y))
+        } else {
+            None
+        }
+    }
+
+    fn xy_idx_glsp(&self, x: i32, y: i32) -> GResult<usize> {
+        match self.try_xy_idx(x, y) {
+            Some(idx) => Ok(idx),
+            None => bail!(
+                "Position ({}, {}) is out of the map ({}x{})",
+                x,
+                y,
+                self.width,
+                self.height
+            ),
+        }
+    }
+
+    /// Errors if an index from glsp is out of the map
+    fn check_idx(&self, idx: usize) -> GResult<()> {
+        if idx >= self.tiles.len() {
+            bail!(
+                "Index {} is out of the map ({} tiles)",
+                idx,
+                self.tiles.len()
+            );
+        }
+        Ok(())
+    }
+
+    fn check_rect(&self, rect: &Rect) -> GResult<()> {
+        if !self.in_bounds(rect.x1, rect.y1) || !self.in_bounds(rect.x2 - 1, rect.y2 - 1) {
+            bail!(
+                "Rect ({}, {}) - ({}, {}) is out of the map ({}x{})",
+                rect.x1,
+                rect.y1,
+                rect.x2,
+                rect.y2,
+                self.width,
+                self.height
+            );
+        }
+        Ok(())
+    }
+
+    /// Indexes of the orthogonal neighbours inside the map
+    pub fn neighbours_4(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        self.neighbours(idx, &[(0, -1), (0, 1), (-1, 0), (1, 0)])
+    }
+
+    /// Indexes of the orthogonal and diagonal neighbours inside the map
+    pub fn neighbours_8(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        self.neighbours(
+            idx,
+            &[
+                (0, -1),
+                (0, 1),
+                (-1, 0),
+                (1, 0),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ],
+        )
+    }
+
+    fn neighbours_glsp(&self, idx: usize, diagonals: bool) -> GResult<Vec<usize>> {
+        self.check_idx(idx)?;
+        if diagonals {
+            Ok(self.neighbours_8(idx).collect())
+        } else {
+            Ok(self.neighbours_4(idx).collect())
+        }
+    }
+
+    fn neighbours(
+        &self,
+        idx: usize,
+        directions: &'static [(i32, i32)],
+    ) -> impl Iterator<Item = usize> + '_ {
+        let pos = self.idx_xy(idx as i32);
+        directions
+            .iter()
+            .filter_map(move |(dx, dy)| self.try_xy_idx(pos.x + dx, pos.y + dy))
+    }
+
+    pub fn idx_xy(&self, idx: i32) -> Point {
         Point {
             x: idx % self.width,
             y: idx / self.width,
         }
     }
 
-    fn apply_room(&mut self, room: &Rect) {
+    /// Saves the current state of the tiles, if the history is recorded
+    pub fn take_snapshot(&mut self) {
+        if self.record_history {
+            self.history.push(self.tiles.to_vec());
+        }
+    }
+
+    pub fn apply_room(&mut self, room: &Rect) -> GResult<()> {
+        let floor = TileType::named(FLOOR)?;
         for y in room.y1..room.y2 {
             for x in room.x1..room.x2 {
                 let idx = self.xy_idx(x, y);
-                self.tiles[idx] = Tile::floor();
+                self.tiles[idx] = Tile::new(floor);
             }
         }
+        Ok(())
     }
 
-    fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
+    pub fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) -> GResult<()> {
+        let floor = TileType::named(FLOOR)?;
         for x in min(x1, x2)..=max(x1, x2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx as usize] = Tile::floor();
+            if let Some(idx) = self.try_xy_idx(x, y) {
+                self.tiles[idx] = Tile::new(floor);
             }
         }
+        Ok(())
     }
 
-    fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
+    pub fn apply_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) -> GResult<()> {
+        let floor = TileType::named(FLOOR)?;
         for y in min(y1, y2)..=max(y1, y2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx as usize] = Tile::floor();
+            if let Some(idx) = self.try_xy_idx(x, y) {
+                self.tiles[idx] = Tile::new(floor);
             }
         }
+        Ok(())
+    }
+
+    /// Replaces a closed door (or any tile that `opens-to` another) with its opened version
+    fn open_door(&mut self, idx: usize) {
+        if let Some(opened) = self.tiles[idx].opens_to {
+            self.tiles[idx] = Tile::new(opened);
+            self.blocked_tiles[idx] = !self.tiles[idx].walkable;
+            self.invalidate_paths();
+        }
+    }
+
+    /// Replaces a tile by a new one of the given type (glsp fn)
+    fn set_tile(&mut self, idx: usize, name: Sym) -> GResult<()> {
+        self.check_idx(idx)?;
+        let tile_type = match TILE_REGISTRY.lock().unwrap().find(&name.name()) {
+            Some(tile_type) => tile_type,
+            None => bail!("Tile type {} is not registered", name),
+        };
+        self.tiles[idx] = Tile::new(tile_type);
+        self.invalidate_paths();
+        Ok(())
+    }
+
+    /// Returns a Dijkstra map from a list of source tiles (glsp fn)
+    fn dijkstra_glsp(
+        &self,
+        sources: Vec<usize>,
+        honour_blocked: Option<bool>,
+        max_depth: Option<f32>,
+    ) -> GResult<DijkstraMap> {
+        for &idx in sources.iter() {
+            self.check_idx(idx)?;
+        }
+        Ok(DijkstraMap::new(
+            self,
+            &sources,
+            honour_blocked.unwrap_or(false),
+            max_depth.unwrap_or(f32::MAX),
+        ))
+    }
+
+    /// Bumps the revision, so the cached paths are computed again
+    fn invalidate_paths(&mut self) {
+        self.revision += 1;
+        self.path_cache.clear();
+    }
+
+    /// Returns the cheapest path between two tiles (glsp fn).
+    /// Tiles blocked by entities are impassable, unless an `occupied_cost` is given:
+    /// they're then traversed at that extra cost, e.g. to queue behind other monsters.
+    /// Paths are cached until the tiles change, or the blocked tiles are indexed again:
+    /// once per turn, the entities moving in between don't clear the cache
+    fn a_star(
+        &mut self,
+        start: usize,
+        end: usize,
+        occupied_cost: Option<f32>,
+    ) -> GResult<NavigationPath> {
+        self.check_idx(start)?;
+        self.check_idx(end)?;
+        let key = (start, end, self.revision, occupied_cost.map(f32::to_bits));
+        let (success, steps) = match self.path_cache.get(&key) {
+            Some(cached) => cached.clone(),
+            None => {
+                self.occupied_cost = occupied_cost;
+                let path = a_star_search(start, end, &*self);
+                self.occupied_cost = None;
+                self.path_cache
+                    .insert(key, (path.success, path.steps.to_vec()));
+                (path.success, path.steps)
+            }
+        };
+        let mut path = NavigationPath::new();
+        path.destination = end;
+        path.success = success;
+        path.steps = steps;
+        Ok(path)
+    }
+
+    /// Walkable tiles and closed doors can be traversed.
+    /// If `honour_blocked` is set, tiles blocked by entities can't
+    pub fn is_passable(&self, idx: usize, honour_blocked: bool) -> bool {
+        let tile = &self.tiles[idx];
+        if tile.opens_to.is_some() {
+            return true;
+        }
+        tile.walkable && !(honour_blocked && self.blocked_tiles[idx])
+    }
+
+    /// Passable tiles that have not been revealed yet, e.g. for auto-exploration
+    fn unrevealed_tiles(&self) -> Vec<usize> {
+        (0..self.tiles.len())
+            .filter(|&idx| !self.revealed_tiles[idx] && self.is_passable(idx, false))
+            .collect()
     }
 
     // FIXME: merge with is_walkable
     fn is_exit_valid(&self, x: i32, y: i32) -> bool {
-        if x < 1 || x > self.width || y < 1 || y > self.height {
+        if !self.in_bounds(x, y) {
             return false;
         }
         let idx = self.xy_idx(x, y);
+        let tile = &self.tiles[idx];
+        // Closed doors are valid exits, since they're opened when walked into
         !self.blocked_tiles[idx]
+            || tile.opens_to.is_some()
+            || (tile.walkable && self.occupied_cost.is_some())
+    }
+
+    /// Cost of entering a tile: its movement cost, plus the occupied cost
+    /// if an entity stands on it
+    fn exit_cost(&self, idx: usize, step: f32) -> f32 {
+        let tile = &self.tiles[idx];
+        let occupied = if tile.walkable && self.blocked_tiles[idx] {
+            self.occupied_cost.unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        step * tile.cost + occupied
+    }
+
+    /// Returns the indexes of the tiles visible from a position (glsp fn)
+    fn field_of_view_glsp(
+        &self,
+        x: i32,
+        y: i32,
+        range: i32,
+        opts: Option<&FovOptions>,
+    ) -> GResult<Vec<usize>> {
+        self.xy_idx_glsp(x, y)?;
+        let default = FovOptions::default();
+        Ok(fov::field_of_view(
+            self,
+            Point::new(x, y),
+            range,
+            opts.unwrap_or(&default),
+        ))
+    }
+
+    /// Tiles on the line from A to B, A excluded (glsp fn).
+    /// The line stops at the first opaque tile or entity blocking the way,
+    /// which is included: it's where a projectile would hit
+    fn line_of_fire(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> GResult<Vec<usize>> {
+        self.xy_idx_glsp(x1, y1)?;
+        self.xy_idx_glsp(x2, y2)?;
+        let mut line = vec![];
+        for pos in geometry::bresenham(Point::new(x1, y1), Point::new(x2, y2))
+            .into_iter()
+            .skip(1)
+        {
+            let idx = self.xy_idx(pos.x, pos.y);
+            line.push(idx);
+            let tile = &self.tiles[idx];
+            if tile.opaque || (tile.walkable && self.blocked_tiles[idx]) {
+                break;
+            }
+        }
+        Ok(line)
     }
 
-    fn field_of_view_glsp(&self, x: i32, y: i32, range: i32) -> Vec<Point> {
-        field_of_view(Point { x, y }, range, self)
+    /// Tiles in a circle, e.g. for an area of effect (glsp fn).
+    /// If `los` is set (the default), only the tiles seen from the center are returned
+    fn tiles_in_radius(
+        &self,
+        x: i32,
+        y: i32,
+        radius: i32,
+        los: Option<bool>,
+    ) -> GResult<Vec<usize>> {
+        self.xy_idx_glsp(x, y)?;
+        if los.unwrap_or(true) {
+            return Ok(fov::field_of_view(
+                self,
+                Point::new(x, y),
+                radius,
+                &FovOptions::default(),
+            ));
+        }
+        let mut tiles = vec![];
+        for ty in y - radius..=y + radius {
+            for tx in x - radius..=x + radius {
+                if let Some(idx) = self.try_xy_idx(tx, ty) {
+                    if RadiusShape::Circle.contains(tx - x, ty - y, radius) {
+                        tiles.push(idx);
+                    }
+                }
+            }
+        }
+        Ok(tiles)
     }
 
-    fn add_tile_to_revealed(&mut self, idx: usize) {
+    /// Tiles seen from A, in the cone pointing towards B, `angle` degrees wide (glsp fn)
+    fn tiles_in_cone(
+        &self,
+        x1: i32,
+        y1: i32,
+        x2: i32,
+        y2: i32,
+        range: i32,
+        angle: f32,
+    ) -> GResult<Vec<usize>> {
+        self.xy_idx_glsp(x1, y1)?;
+        let (origin, target) = (Point::new(x1, y1), Point::new(x2, y2));
+        Ok(
+            fov::field_of_view(self, origin, range, &FovOptions::default())
+                .into_iter()
+                .filter(|&idx| geometry::in_cone(origin, target, self.idx_xy(idx as i32), angle))
+                .collect(),
+        )
+    }
+
+    /// Whether B can be seen from A, within a range (glsp fn).
+    /// The sight is symmetric, so it's also whether A can be seen from B
+    fn is_visible_from(
+        &self,
+        x1: i32,
+        y1: i32,
+        x2: i32,
+        y2: i32,
+        range: Option<i32>,
+    ) -> GResult<bool> {
+        self.xy_idx_glsp(x1, y1)?;
+        let target = self.xy_idx_glsp(x2, y2)?;
+        let (origin, end) = (Point::new(x1, y1), Point::new(x2, y2));
+        let range =
+            range.unwrap_or_else(|| DistanceAlg::Pythagoras.distance2d(origin, end).ceil() as i32);
+        Ok(fov::field_of_view(self, origin, range, &FovOptions::default()).contains(&target))
+    }
+
+    /// Total light received by a tile
+    pub fn light_at(&self, idx: usize) -> RGB {
+        self.ambient_light + self.light[idx]
+    }
+
+    pub fn is_lit(&self, idx: usize) -> bool {
+        lighting::is_lit(self.light_at(idx))
+    }
+
+    /// Clears the light buffer, keeping the previous one to detect the changes
+    fn clear_light(&mut self) {
+        std::mem::swap(&mut self.light, &mut self.previous_light);
+        self.light.iter_mut().for_each(|l| *l = RGB::named(BLACK));
+    }
+
+    /// Casts the light of a source (glsp fn).
+    /// `falloff` is how fast the light fades with the distance, 1 being linear
+    fn add_light(
+        &mut self,
+        x: i32,
+        y: i32,
+        radius: i32,
+        color: &RGB,
+        falloff: Option<f32>,
+    ) -> GResult<()> {
+        self.xy_idx_glsp(x, y)?;
+        lighting::cast_light(
+            self,
+            Point::new(x, y),
+            radius,
+            *color,
+            falloff.unwrap_or(1.0),
+        );
+        Ok(())
+    }
+
+    fn add_tile_to_revealed(&mut self, idx: usize) -> GResult<()> {
+        self.check_idx(idx)?;
         self.revealed_tiles[idx] = true;
+        Ok(())
     }
 
-    fn add_tile_to_visible(&mut self, idx: usize) {
+    fn add_tile_to_visible(&mut self, idx: usize) -> GResult<()> {
+        self.check_idx(idx)?;
         self.visible_tiles[idx] = true;
+        Ok(())
     }
 
-    fn populate_blocked_tiles(&mut self) {
+    /// Resets the blocked tiles to the non walkable ones, clearing the cached paths
+    pub fn populate_blocked_tiles(&mut self) {
         for (i, tile) in self.tiles.iter().enumerate() {
-            self.blocked_tiles[i] = tile.tile_type == TileType::Wall;
+            self.blocked_tiles[i] = !tile.walkable;
         }
+        self.invalidate_paths();
+    }
+
+    fn block_tile(&mut self, idx: usize) -> GResult<()> {
+        self.check_idx(idx)?;
+        self.blocked_tiles[idx] = true;
+        Ok(())
+    }
+
+    /// Plain text rendering of the revealed tiles, one string per row:
+    /// `#` for walls, `+` for closed doors, `.` for the other walkable tiles
+    pub fn ascii_snapshot(&self) -> Vec<String> {
+        self.tiles
+            .chunks(self.width as usize)
+            .zip(self.revealed_tiles.chunks(self.width as usize))
+            .map(|(row, revealed)| {
+                row.iter()
+                    .zip(revealed)
+                    .map(|(tile, &revealed)| {
+                        if !revealed {
+                            ' '
+                        } else if tile.opens_to.is_some() {
+                            '+'
+                        } else if tile.walkable {
+                            '.'
+                        } else {
+                            '#'
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Frees a tile after an entity moved away from it, unless the tile itself blocks.
+    /// Keeps the map up to date between two indexings
+    fn unblock_tile(&mut self, idx: usize) -> GResult<()> {
+        self.check_idx(idx)?;
+        self.blocked_tiles[idx] = !self.tiles[idx].walkable;
+        Ok(())
     }
 
     fn clear_content_index(&mut self) {
@@ -180,8 +690,15 @@ impl Map {
         }
     }
 
-    fn index_entity(&mut self, idx: usize, entity: Entity) {
+    fn entities_at(&self, idx: usize) -> GResult<Vec<Entity>> {
+        self.check_idx(idx)?;
+        Ok(self.tile_content[idx].to_vec())
+    }
+
+    fn index_entity(&mut self, idx: usize, entity: Entity) -> GResult<()> {
+        self.check_idx(idx)?;
         self.tile_content[idx].push(entity);
+        Ok(())
     }
 }
 
@@ -196,41 +713,35 @@ impl Algorithm2D for Map {
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx].tile_type == TileType::Wall
+        self.tiles[idx].opaque
     }
 
     fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
         let mut exits = SmallVec::new();
         let x = idx as i32 % self.width;
         let y = idx as i32 / self.width;
-        let w = self.width as usize;
-
-        // Cardinal directions
-        if self.is_exit_valid(x - 1, y) {
-            exits.push((idx - 1, 1.0))
-        };
-        if self.is_exit_valid(x + 1, y) {
-            exits.push((idx + 1, 1.0))
-        };
-        if self.is_exit_valid(x, y - 1) {
-            exits.push((idx - w, 1.0))
-        };
-        if self.is_exit_valid(x, y + 1) {
-            exits.push((idx + w, 1.0))
-        };
 
-        // Diagonals
-        if self.is_exit_valid(x - 1, y - 1) {
-            exits.push(((idx - w) - 1, 1.45));
-        }
-        if self.is_exit_valid(x + 1, y - 1) {
-            exits.push(((idx - w) + 1, 1.45));
-        }
-        if self.is_exit_valid(x - 1, y + 1) {
-            exits.push(((idx + w) - 1, 1.45));
-        }
-        if self.is_exit_valid(x + 1, y + 1) {
-            exits.push(((idx + w) + 1, 1.45));
+        for (dx, dy) in [
+            // Cardinal directions
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            // Diagonals
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+        ] {
+            if self.is_exit_valid(x + dx, y + dy) {
+                let step = if dx != 0 && dy != 0 {
+                    DIAGONAL_COST
+                } else {
+                    1.0
+                };
+                let exit = self.xy_idx(x + dx, y + dy);
+                exits.push((exit, self.exit_cost(exit, step)));
+            }
         }
 
         exits
@@ -244,29 +755,261 @@ impl BaseMap for Map {
     }
 }
 
-pub fn draw_map(map: &Map) {
-    let mut x = 0;
-    let mut y = 0;
-    // let wall = sym("wall").unwrap();
-    // let floor = sym("floor").unwrap();
-    for (idx, tile) in map.tiles.iter().enumerate() {
-        if map.revealed_tiles[idx] {
-            let fg = if map.visible_tiles[idx] {
-                tile.fg
-            } else {
-                tile.fg_fog
-            };
-            let bg = if map.visible_tiles[idx] {
-                tile.bg
-            } else {
-                tile.bg_fog
-            };
-            set_char(x, y, tile.glyph, &fg, &bg, tile.console);
+/// Draws the part of the map that is inside the camera viewport (glsp fn).
+/// If a snapshot index is given, draws that step of the generation history instead,
+/// with all the tiles revealed
+pub fn draw_map(map: &Map, snapshot: Option<usize>) -> GResult<()> {
+    let tiles = match snapshot {
+        Some(step) => match map.history.get(step) {
+            Some(tiles) => tiles,
+            None => bail!(
+                "Snapshot {} does not exist ({} snapshots)",
+                step,
+                map.history.len()
+            ),
+        },
+        None => &map.tiles,
+    };
+    let camera = Camera::borrow();
+    for y in 0..camera.height {
+        for x in 0..camera.width {
+            let world = camera.screen_to_world(x, y);
+            if let Some(idx) = map.try_xy_idx(world.x, world.y) {
+                draw_tile(map, &tiles[idx], idx, x, y, snapshot.is_some());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn draw_tile(map: &Map, tile: &Tile, idx: usize, x: i32, y: i32, reveal_all: bool) {
+    if reveal_all {
+        set_char(x, y, tile.glyph, &tile.fg, &tile.bg, tile.console);
+    } else if map.revealed_tiles[idx] {
+        let glyph = match tile.autotile {
+            Some(glyph) if autotile::touches_revealed_floor(map, idx) => glyph,
+            _ => tile.glyph,
+        };
+        // visible tiles are tinted by the light they receive
+        let (fg, bg) = if map.visible_tiles[idx] {
+            let light = map.light_at(idx);
+            (
+                lighting::tint(tile.fg, light),
+                lighting::tint(tile.bg, light),
+            )
+        } else {
+            (tile.fg_fog, tile.bg_fog)
+        };
+        set_char(x, y, glyph, &fg, &bg, tile.console);
+    }
+}
+
+/// Map fixtures shared by the tests of the modules working on maps
+#[cfg(test)]
+pub mod test_utils {
+    use std::sync::Once;
+
+    use super::*;
+
+    static REGISTER_TILES: Once = Once::new();
+
+    /// Registers the tiles used by the tests, once: the tests run in parallel
+    /// and share the global registry, which reads the defs when the tiles are drawn
+    pub fn register_tiles() {
+        REGISTER_TILES.call_once(|| {
+            let mut registry = TILE_REGISTRY.lock().unwrap();
+            let mut wall = TileDef::new(WALL);
+            wall.walkable = false;
+            wall.opaque = true;
+            wall.autotile = Some((100..116).collect());
+            registry.register(wall);
+            registry.register(TileDef::new(FLOOR));
+        });
+    }
+
+    /// Builds a map from rows of `#` (walls), any other char being a floor
+    pub fn map_from(rows: &[&str]) -> Map {
+        register_tiles();
+        let mut map = Map::new(rows[0].len() as i32, rows.len() as i32).unwrap();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c != '#' {
+                    let idx = map.xy_idx(x as i32, y as i32);
+                    map.tiles[idx] = Tile::named(FLOOR).unwrap();
+                }
+            }
         }
-        x += 1;
-        if x > (map.width - 1) {
-            x = 0;
-            y += 1;
+        map.populate_blocked_tiles();
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::{map_from, register_tiles};
+    use super::*;
+
+    #[test]
+    fn test_in_bounds() {
+        let map = map_from(&[".........."; 5]);
+        assert!(map.in_bounds(0, 0));
+        assert!(map.in_bounds(9, 4));
+        assert!(!map.in_bounds(-1, 0));
+        assert!(!map.in_bounds(0, -1));
+        assert!(!map.in_bounds(10, 0));
+        assert!(!map.in_bounds(0, 5));
+    }
+
+    #[test]
+    fn test_try_xy_idx() {
+        let map = map_from(&[".........."; 5]);
+        assert_eq!(map.try_xy_idx(0, 0), Some(0));
+        assert_eq!(map.try_xy_idx(9, 4), Some(49));
+        // would wrap to the next row with xy_idx
+        assert_eq!(map.try_xy_idx(10, 0), None);
+        assert_eq!(map.try_xy_idx(-1, 1), None);
+        assert_eq!(map.try_xy_idx(0, 5), None);
+    }
+
+    #[test]
+    fn test_neighbours_corners() {
+        let map = map_from(&[".........."; 5]);
+        let top_left = map.xy_idx(0, 0);
+        let mut n4 = map.neighbours_4(top_left).collect::<Vec<_>>();
+        n4.sort_unstable();
+        assert_eq!(n4, vec![1, 10]);
+        let mut n8 = map.neighbours_8(top_left).collect::<Vec<_>>();
+        n8.sort_unstable();
+        assert_eq!(n8, vec![1, 10, 11]);
+
+        let bottom_right = map.xy_idx(9, 4);
+        assert_eq!(map.neighbours_4(bottom_right).count(), 2);
+        assert_eq!(map.neighbours_8(bottom_right).count(), 3);
+    }
+
+    #[test]
+    fn test_neighbours_edges() {
+        let map = map_from(&[".........."; 5]);
+        // right edge: nothing from the next row
+        let right = map.xy_idx(9, 2);
+        let n8 = map.neighbours_8(right).collect::<Vec<_>>();
+        assert_eq!(n8.len(), 5);
+        assert!(n8.iter().all(|&idx| map.idx_xy(idx as i32).x >= 8));
+        assert_eq!(map.neighbours_4(map.xy_idx(5, 0)).count(), 3);
+        assert_eq!(map.neighbours_8(map.xy_idx(5, 2)).count(), 8);
+    }
+
+    #[test]
+    fn test_line_of_fire_stops() {
+        let mut map = map_from(&[".........."; 5]);
+        let wall = map.xy_idx(6, 2);
+        map.tiles[wall] = Tile::named(WALL).unwrap();
+        map.populate_blocked_tiles();
+        let line = map.line_of_fire(1, 2, 9, 2).unwrap();
+        assert_eq!(line.first(), Some(&map.xy_idx(2, 2)));
+        assert_eq!(line.last(), Some(&wall));
+
+        // an entity stops the line too
+        let monster = map.xy_idx(4, 2);
+        map.blocked_tiles[monster] = true;
+        let line = map.line_of_fire(1, 2, 9, 2).unwrap();
+        assert_eq!(line, vec![map.xy_idx(2, 2), map.xy_idx(3, 2), monster]);
+    }
+
+    #[test]
+    fn test_unblock_tile() {
+        let mut map = map_from(&[".........."; 5]);
+        let floor = map.xy_idx(2, 2);
+        map.block_tile(floor).unwrap();
+        assert!(map.blocked_tiles[floor]);
+        map.unblock_tile(floor).unwrap();
+        assert!(!map.blocked_tiles[floor]);
+
+        // walls stay blocked
+        let wall = map.xy_idx(3, 2);
+        map.tiles[wall] = Tile::named(WALL).unwrap();
+        map.populate_blocked_tiles();
+        map.unblock_tile(wall).unwrap();
+        assert!(map.blocked_tiles[wall]);
+        assert!(map.unblock_tile(50).is_err());
+    }
+
+    #[test]
+    fn test_map_size() {
+        register_tiles();
+        assert_eq!(Map::new(3, 2).unwrap().tiles.len(), 6);
+        assert!(Map::new(0, 5).is_err());
+        assert!(Map::new(5, -1).is_err());
+        assert!(Map::new(MAX_MAP_SIZE + 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_moves_keep_the_path_cache() {
+        let mut map = map_from(&[".........."; 5]);
+        let revision = map.revision;
+        map.block_tile(12).unwrap();
+        map.unblock_tile(12).unwrap();
+        assert_eq!(map.revision, revision);
+        // once per turn
+        map.populate_blocked_tiles();
+        assert_eq!(map.revision, revision + 1);
+    }
+
+    #[test]
+    fn test_path_avoids_costly_tiles() {
+        let mut map = map_from(&[".........."; 5]);
+        let costly = map.xy_idx(2, 2);
+        map.tiles[costly].cost = 10.0;
+        let path = map
+            .a_star(map.xy_idx(0, 2), map.xy_idx(4, 2), None)
+            .unwrap();
+        assert!(path.success);
+        assert!(!path.steps.contains(&costly));
+    }
+
+    #[test]
+    fn test_path_occupied_cost() {
+        // an occupied tile is avoided when there's another way
+        let mut map = map_from(&[".........."; 5]);
+        let occupied = map.xy_idx(2, 2);
+        map.block_tile(occupied).unwrap();
+        let path = map
+            .a_star(map.xy_idx(0, 2), map.xy_idx(4, 2), Some(5.0))
+            .unwrap();
+        assert!(path.success);
+        assert!(!path.steps.contains(&occupied));
+
+        // and traversed when it's the only one
+        let mut map = map_from(&["#####", "#...#", "#####"]);
+        let occupied = map.xy_idx(2, 1);
+        map.block_tile(occupied).unwrap();
+        let (start, end) = (map.xy_idx(1, 1), map.xy_idx(3, 1));
+        assert!(!map.a_star(start, end, None).unwrap().success);
+        let path = map.a_star(start, end, Some(5.0)).unwrap();
+        assert!(path.success);
+        assert!(path.steps.contains(&occupied));
+    }
+
+    #[test]
+    fn test_ascii_snapshot() {
+        let mut map = map_from(&["...."; 2]);
+        map.tiles[1] = Tile::named(WALL).unwrap();
+        for idx in 0..3 {
+            map.revealed_tiles[idx] = true;
         }
+        assert_eq!(map.ascii_snapshot(), vec![".#. ", "    "]);
+    }
+
+    #[test]
+    fn test_exits_stay_inside() {
+        let map = map_from(&[".........."; 5]);
+        assert!(map.is_exit_valid(0, 0));
+        assert!(map.is_exit_valid(9, 4));
+        assert!(!map.is_exit_valid(10, 4));
+        assert!(!map.is_exit_valid(9, 5));
+        assert!(!map.is_exit_valid(-1, 0));
+        assert_eq!(map.get_available_exits(map.xy_idx(0, 0)).len(), 3);
+        assert_eq!(map.get_available_exits(map.xy_idx(9, 4)).len(), 3);
+        assert_eq!(map.get_available_exits(map.xy_idx(9, 2)).len(), 5);
     }
 }
@@ -0,0 +1,31 @@
+use bracket_lib::prelude::*;
+use glsp::prelude::*;
+
+use super::MetaMapBuilder;
+use crate::map::Map;
+
+/// Sets the starting position on the walkable tile closest to the center of the map.
+/// Useful for maps without rooms
+pub struct AreaStartBuilder {}
+
+impl MetaMapBuilder for AreaStartBuilder {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        let center = Point::new(map.width / 2, map.height / 2);
+        let closest = map
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.walkable)
+            .map(|(idx, _)| {
+                let pos = map.idx_xy(idx as i32);
+                (pos, DistanceAlg::PythagorasSquared.distance2d(center, pos))
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        match closest {
+            Some((pos, _)) => map.start = Some(pos),
+            None => bail!("area-start needs a map with walkable tiles"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use bracket_lib::prelude::*;
+use glsp::GResult;
+
+use super::InitialMapBuilder;
+use crate::{
+    map::Map,
+    tile::{Tile, FLOOR},
+    RNG,
+};
+
+/// Binary Space Partition rooms, connected by corridors
+pub struct BspBuilder {
+    rects: Vec<Rect>,
+}
+
+impl BspBuilder {
+    pub fn new() -> Self {
+        BspBuilder { rects: vec![] }
+    }
+
+    /// Splits a rect into 4 quadrants
+    fn add_subrects(&mut self, rect: Rect) {
+        let w = i32::abs(rect.x1 - rect.x2);
+        let h = i32::abs(rect.y1 - rect.y2);
+        let half_w = i32::max(w / 2, 1);
+        let half_h = i32::max(h / 2, 1);
+
+        self.rects
+            .push(Rect::with_size(rect.x1, rect.y1, half_w, half_h));
+        self.rects
+            .push(Rect::with_size(rect.x1, rect.y1 + half_h, half_w, half_h));
+        self.rects
+            .push(Rect::with_size(rect.x1 + half_w, rect.y1, half_w, half_h));
+        self.rects.push(Rect::with_size(
+            rect.x1 + half_w,
+            rect.y1 + half_h,
+            half_w,
+            half_h,
+        ));
+    }
+
+    fn get_random_rect(&self) -> Rect {
+        if self.rects.len() == 1 {
+            return self.rects[0];
+        }
+        let idx = RNG.lock().unwrap().range(0, self.rects.len() as i32) as usize;
+        self.rects[idx]
+    }
+
+    /// Returns a randomly sized room inside `rect`
+    fn get_random_sub_rect(&self, rect: Rect) -> Rect {
+        let mut rng = RNG.lock().unwrap();
+        let rect_width = i32::abs(rect.x1 - rect.x2);
+        let rect_height = i32::abs(rect.y1 - rect.y2);
+
+        let w = i32::max(3, rng.roll_dice(1, i32::min(rect_width, 10)) - 1) + 1;
+        let h = i32::max(3, rng.roll_dice(1, i32::min(rect_height, 10)) - 1) + 1;
+        let x = rect.x1 + rng.roll_dice(1, 6) - 1;
+        let y = rect.y1 + rng.roll_dice(1, 6) - 1;
+        Rect::with_size(x, y, w, h)
+    }
+
+    /// A room can be placed if it's inside the map,
+    /// and if it doesn't touch another room
+    fn is_possible(&self, rect: Rect, map: &Map) -> bool {
+        let expanded = Rect::with_exact(rect.x1 - 2, rect.y1 - 2, rect.x2 + 2, rect.y2 + 2);
+        for y in expanded.y1..=expanded.y2 {
+            for x in expanded.x1..=expanded.x2 {
+                if x < 1 || y < 1 || x > map.width - 2 || y > map.height - 2 {
+                    return false;
+                }
+                let idx = map.xy_idx(x, y);
+                if map.tiles[idx].walkable {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn draw_corridor(map: &mut Map, x1: i32, y1: i32, x2: i32, y2: i32) -> GResult<()> {
+        let mut x = x1;
+        let mut y = y1;
+        while x != x2 || y != y2 {
+            if x < x2 {
+                x += 1;
+            } else if x > x2 {
+                x -= 1;
+            } else if y < y2 {
+                y += 1;
+            } else if y > y2 {
+                y -= 1;
+            }
+            let idx = map.xy_idx(x, y);
+            if !map.tiles[idx].walkable {
+                map.tiles[idx] = Tile::named(FLOOR)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InitialMapBuilder for BspBuilder {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        self.rects.clear();
+        self.rects
+            .push(Rect::with_size(2, 2, map.width - 5, map.height - 5));
+        let first_room = self.rects[0];
+        self.add_subrects(first_room);
+
+        // Try up to 240 times to place a room
+        for _ in 0..240 {
+            let rect = self.get_random_rect();
+            let candidate = self.get_random_sub_rect(rect);
+            if self.is_possible(candidate, map) {
+                map.apply_room(&candidate)?;
+                map.add_room(&candidate);
+                map.take_snapshot();
+                self.add_subrects(rect);
+            }
+        }
+
+        // Connect the rooms from left to right
+        map.rooms.sort_by(|a, b| a.x1.cmp(&b.x1));
+        for i in 1..map.rooms.len() {
+            let room = map.rooms[i - 1];
+            let next_room = map.rooms[i];
+            let (start_x, start_y) = {
+                let mut rng = RNG.lock().unwrap();
+                (
+                    room.x1 + rng.roll_dice(1, i32::abs(room.x1 - room.x2)) - 1,
+                    room.y1 + rng.roll_dice(1, i32::abs(room.y1 - room.y2)) - 1,
+                )
+            };
+            let (end_x, end_y) = {
+                let mut rng = RNG.lock().unwrap();
+                (
+                    next_room.x1 + rng.roll_dice(1, i32::abs(next_room.x1 - next_room.x2)) - 1,
+                    next_room.y1 + rng.roll_dice(1, i32::abs(next_room.y1 - next_room.y2)) - 1,
+                )
+            };
+            BspBuilder::draw_corridor(map, start_x, start_y, end_x, end_y)?;
+            map.take_snapshot();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use glsp::GResult;
+
+use super::InitialMapBuilder;
+use crate::{
+    map::Map,
+    tile::{Tile, TileType, FLOOR, WALL},
+    RNG,
+};
+
+/// Cave-like maps, carved with a cellular automaton
+pub struct CellularAutomataBuilder {
+    pub iterations: i32,
+    /// Chance (in %) for a tile to start as a floor
+    pub floor_chance: i32,
+}
+
+impl InitialMapBuilder for CellularAutomataBuilder {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        let floor = TileType::named(FLOOR)?;
+        let wall = TileType::named(WALL)?;
+
+        // Random noise
+        for y in 1..map.height - 1 {
+            for x in 1..map.width - 1 {
+                let roll = RNG.lock().unwrap().roll_dice(1, 100);
+                let idx = map.xy_idx(x, y);
+                if roll <= self.floor_chance {
+                    map.tiles[idx] = Tile::new(floor);
+                }
+            }
+        }
+        map.take_snapshot();
+
+        // Smooth it: a tile becomes a wall if it has too many (or no) wall neighbours
+        for _ in 0..self.iterations {
+            let walls = map
+                .tiles
+                .iter()
+                .map(|tile| !tile.walkable)
+                .collect::<Vec<_>>();
+            for y in 1..map.height - 1 {
+                for x in 1..map.width - 1 {
+                    let mut neighbours = 0;
+                    for (dx, dy) in [
+                        (-1, -1),
+                        (0, -1),
+                        (1, -1),
+                        (-1, 0),
+                        (1, 0),
+                        (-1, 1),
+                        (0, 1),
+                        (1, 1),
+                    ] {
+                        if walls[map.xy_idx(x + dx, y + dy)] {
+                            neighbours += 1;
+                        }
+                    }
+
+                    let idx = map.xy_idx(x, y);
+                    let becomes_wall = neighbours > 4 || neighbours == 0;
+                    // Only replace tiles that change, to keep their glyph
+                    if becomes_wall && !walls[idx] {
+                        map.tiles[idx] = Tile::new(wall);
+                    } else if !becomes_wall && walls[idx] {
+                        map.tiles[idx] = Tile::new(floor);
+                    }
+                }
+            }
+            map.take_snapshot();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use bracket_lib::prelude::*;
+use glsp::prelude::*;
+
+use super::MetaMapBuilder;
+use crate::{
+    map::Map,
+    tile::{Tile, TileType, WALL},
+};
+
+/// Fills with walls all the walkable tiles that can't be reached from the starting position
+pub struct CullUnreachable {}
+
+impl MetaMapBuilder for CullUnreachable {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        let start = match map.start {
+            Some(start) => start,
+            None => bail!("cull-unreachable needs a starting position"),
+        };
+        let start_idx = map.xy_idx(start.x, start.y);
+        map.populate_blocked_tiles();
+        let dijkstra = DijkstraMap::new(map.width, map.height, &[start_idx], &*map, 1000.0);
+        let wall = TileType::named(WALL)?;
+
+        for (idx, tile) in map.tiles.iter_mut().enumerate() {
+            if tile.walkable && dijkstra.map[idx] == f32::MAX {
+                *tile = Tile::new(wall);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_utils::map_from;
+
+    #[test]
+    fn test_isolated_floor_becomes_wall() {
+        let mut map = map_from(&["#####", "#.#.#", "#####"]);
+        map.start = Some(Point::new(1, 1));
+        CullUnreachable {}.build_map(&mut map).unwrap();
+        assert!(map.tiles[map.xy_idx(1, 1)].walkable);
+        assert!(!map.tiles[map.xy_idx(3, 1)].walkable);
+    }
+}
@@ -0,0 +1,58 @@
+use bracket_lib::prelude::*;
+use glsp::prelude::*;
+
+use super::MetaMapBuilder;
+use crate::map::Map;
+
+/// Places the exit on the reachable tile that is the farthest from the start
+pub struct DistantExit {}
+
+impl MetaMapBuilder for DistantExit {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        let start = match map.start {
+            Some(start) => start,
+            None => bail!("distant-exit needs a starting position"),
+        };
+        let start_idx = map.xy_idx(start.x, start.y);
+        map.populate_blocked_tiles();
+        let dijkstra = DijkstraMap::new(map.width, map.height, &[start_idx], &*map, 1000.0);
+
+        let mut exit: Option<(usize, f32)> = None;
+        for (idx, tile) in map.tiles.iter().enumerate() {
+            let distance = dijkstra.map[idx];
+            if !tile.walkable || distance == f32::MAX {
+                continue;
+            }
+            match exit {
+                Some((_, best)) if best >= distance => {}
+                _ => exit = Some((idx, distance)),
+            }
+        }
+
+        match exit {
+            Some((idx, _)) => map.exit = Some(map.idx_xy(idx as i32)),
+            None => bail!("distant-exit could not find a reachable tile"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::test_utils::map_from;
+
+    #[test]
+    fn test_exit_is_the_farthest_reachable_tile() {
+        let mut map = map_from(&[
+            "##########",
+            "#........#",
+            "#.########",
+            "#.#......#",
+            "##########",
+        ]);
+        map.start = Some(Point::new(1, 1));
+        DistantExit {}.build_map(&mut map).unwrap();
+        assert_eq!(map.exit, Some(Point::new(8, 1)));
+    }
+}
@@ -0,0 +1,67 @@
+use glsp::prelude::*;
+
+use super::MetaMapBuilder;
+use crate::{
+    map::Map,
+    tile::{Tile, DOOR_CLOSED},
+    RNG,
+};
+
+/// Places closed doors where corridors enter the rooms
+pub struct DoorPlacement {
+    /// Chance (in %) for each junction to get a door
+    pub chance: i32,
+}
+
+impl DoorPlacement {
+    /// A door can only be placed on a floor tile between 2 walls,
+    /// either horizontally or vertically
+    fn is_door_possible(map: &Map, idx: usize) -> bool {
+        let pos = map.idx_xy(idx as i32);
+        if pos.x < 1 || pos.y < 1 || pos.x > map.width - 2 || pos.y > map.height - 2 {
+            return false;
+        }
+        let w = map.width as usize;
+        let floor = |idx: usize| map.tiles[idx].walkable && !map.tiles[idx].opaque;
+        let wall = |idx: usize| !map.tiles[idx].walkable && map.tiles[idx].opaque;
+
+        let east_west = floor(idx - 1) && floor(idx + 1) && wall(idx - w) && wall(idx + w);
+        let north_south = wall(idx - 1) && wall(idx + 1) && floor(idx - w) && floor(idx + w);
+
+        floor(idx) && (east_west || north_south)
+    }
+}
+
+impl MetaMapBuilder for DoorPlacement {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        if map.rooms.is_empty() {
+            bail!("doors needs a map with rooms");
+        }
+
+        // Junctions are on the ring of tiles surrounding each room
+        let mut junctions = vec![];
+        for room in map.rooms.iter() {
+            for x in room.x1..room.x2 {
+                junctions.push((x, room.y1 - 1));
+                junctions.push((x, room.y2));
+            }
+            for y in room.y1..room.y2 {
+                junctions.push((room.x1 - 1, y));
+                junctions.push((room.x2, y));
+            }
+        }
+
+        for (x, y) in junctions {
+            let idx = match map.try_xy_idx(x, y) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            if DoorPlacement::is_door_possible(map, idx)
+                && RNG.lock().unwrap().roll_dice(1, 100) <= self.chance
+            {
+                map.tiles[idx] = Tile::named(DOOR_CLOSED)?;
+            }
+        }
+        Ok(())
+    }
+}
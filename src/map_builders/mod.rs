@@ -0,0 +1,162 @@
+use glsp::prelude::*;
+
+use crate::{autotile, map::Map};
+
+mod area_start;
+mod bsp;
+mod cellular_automata;
+mod cull_unreachable;
+mod distant_exit;
+mod doors;
+mod room_based_start;
+mod room_corner_rounding;
+mod simple_map;
+mod spawn_regions;
+mod symmetry;
+
+use area_start::AreaStartBuilder;
+use bsp::BspBuilder;
+use cellular_automata::CellularAutomataBuilder;
+use cull_unreachable::CullUnreachable;
+use distant_exit::DistantExit;
+use doors::DoorPlacement;
+use room_based_start::RoomBasedStart;
+use room_corner_rounding::RoomCornerRounder;
+use simple_map::SimpleMapBuilder;
+use spawn_regions::SpawnRegions;
+use symmetry::SymmetryBuilder;
+
+/// Generates the base layout of a map (rooms, caves...)
+pub trait InitialMapBuilder {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()>;
+}
+
+/// Post-processes a map generated by an `InitialMapBuilder`
+pub trait MetaMapBuilder {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()>;
+}
+
+pub struct BuilderChain {
+    starter: Box<dyn InitialMapBuilder>,
+    builders: Vec<Box<dyn MetaMapBuilder>>,
+    map: Map,
+}
+
+impl BuilderChain {
+    pub fn new(width: i32, height: i32, starter: Box<dyn InitialMapBuilder>) -> GResult<Self> {
+        Ok(BuilderChain {
+            starter,
+            builders: vec![],
+            map: Map::new(width, height)?,
+        })
+    }
+
+    /// Records a snapshot of the map after each generation step
+    pub fn record_history(&mut self, record: bool) {
+        self.map.record_history = record;
+    }
+
+    pub fn with(&mut self, builder: Box<dyn MetaMapBuilder>) {
+        self.builders.push(builder);
+    }
+
+    pub fn build(mut self) -> GResult<Map> {
+        self.starter.build_map(&mut self.map)?;
+        self.map.take_snapshot();
+        for builder in self.builders.iter_mut() {
+            builder.build_map(&mut self.map)?;
+            self.map.take_snapshot();
+        }
+        autotile::autotile(&mut self.map);
+        Ok(self.map)
+    }
+}
+
+pub fn bind_map_builders() -> GResult<()> {
+    glsp::bind_rfn("map:build", &build)?;
+    Ok(())
+}
+
+/// Builds a map from a list of steps (glsp fn).
+/// The first step must be an initial builder, the others are meta builders:
+///
+/// `(map:build 80 40 '((bsp) (cull-unreachable) (room-start) (distant-exit)))`
+///
+/// Set `record-history` to keep a snapshot of each step, to be drawn with `(draw-map map step)`
+fn build(width: i32, height: i32, steps: Vec<Val>, record_history: Option<bool>) -> GResult<Map> {
+    let mut steps = steps.iter();
+    let starter = match steps.next() {
+        Some(step) => {
+            let (name, params) = parse_step(step)?;
+            initial_builder(&name, &params)?
+        }
+        None => bail!("map:build needs at least an initial builder"),
+    };
+    let mut chain = BuilderChain::new(width, height, starter)?;
+    chain.record_history(record_history.unwrap_or(false));
+    for step in steps {
+        let (name, params) = parse_step(step)?;
+        chain.with(meta_builder(&name, &params)?);
+    }
+    chain.build()
+}
+
+/// Splits a step into its name and parameters.
+/// A step is either a symbol, or an array starting with a symbol
+fn parse_step(step: &Val) -> GResult<(String, Vec<Val>)> {
+    match step {
+        Val::Sym(name) => Ok((name.name().to_string(), vec![])),
+        Val::Arr(_) => {
+            let mut items = Vec::<Val>::from_val(step)?;
+            if items.is_empty() {
+                bail!("Empty map builder step");
+            }
+            let name = match items.remove(0) {
+                Val::Sym(name) => name.name().to_string(),
+                val => bail!("Expected a map builder name, received {}", val),
+            };
+            Ok((name, items))
+        }
+        val => bail!("Invalid map builder step: {}", val),
+    }
+}
+
+/// Returns the nth parameter of a step, or a default value
+fn param<T: FromVal>(params: &[Val], n: usize, default: T) -> GResult<T> {
+    match params.get(n) {
+        Some(val) => T::from_val(val),
+        None => Ok(default),
+    }
+}
+
+fn initial_builder(name: &str, params: &[Val]) -> GResult<Box<dyn InitialMapBuilder>> {
+    Ok(match name {
+        "rooms" => Box::new(SimpleMapBuilder {
+            max_rooms: param(params, 0, 30)?,
+            min_size: param(params, 1, 6)?,
+            max_size: param(params, 2, 10)?,
+        }),
+        "bsp" => Box::new(BspBuilder::new()),
+        "caves" => Box::new(CellularAutomataBuilder {
+            iterations: param(params, 0, 15)?,
+            floor_chance: param(params, 1, 45)?,
+        }),
+        _ => bail!("Unknown initial map builder: {}", name),
+    })
+}
+
+fn meta_builder(name: &str, params: &[Val]) -> GResult<Box<dyn MetaMapBuilder>> {
+    Ok(match name {
+        "cull-unreachable" => Box::new(CullUnreachable {}),
+        "room-start" => Box::new(RoomBasedStart {}),
+        "area-start" => Box::new(AreaStartBuilder {}),
+        "distant-exit" => Box::new(DistantExit {}),
+        "doors" => Box::new(DoorPlacement {
+            chance: param(params, 0, 100)?,
+        }),
+        "symmetry" => Box::new(SymmetryBuilder::new(param(params, 0, glsp::sym("both")?)?)?),
+        "round-rooms" => Box::new(RoomCornerRounder {}),
+        "spawn-regions" => Box::new(SpawnRegions::new(param(params, 0, glsp::sym("rooms")?)?)?),
+        _ => bail!("Unknown meta map builder: {}", name),
+    })
+}
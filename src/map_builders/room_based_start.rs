@@ -0,0 +1,17 @@
+use glsp::prelude::*;
+
+use super::MetaMapBuilder;
+use crate::map::Map;
+
+/// Sets the starting position at the center of the first room
+pub struct RoomBasedStart {}
+
+impl MetaMapBuilder for RoomBasedStart {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        match map.rooms.first() {
+            Some(room) => map.start = Some(room.center()),
+            None => bail!("room-start needs a map with rooms"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use glsp::prelude::*;
+
+use super::MetaMapBuilder;
+use crate::{
+    map::Map,
+    tile::{Tile, WALL},
+};
+
+/// Fills the corners of the rooms with walls
+pub struct RoomCornerRounder {}
+
+impl RoomCornerRounder {
+    fn fill_if_corner(map: &mut Map, x: i32, y: i32) -> GResult<()> {
+        let mut neighbour_walls = 0;
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            if let Some(idx) = map.try_xy_idx(x + dx, y + dy) {
+                if !map.tiles[idx].walkable {
+                    neighbour_walls += 1;
+                }
+            }
+        }
+        if neighbour_walls == 2 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = Tile::named(WALL)?;
+        }
+        Ok(())
+    }
+}
+
+impl MetaMapBuilder for RoomCornerRounder {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        if map.rooms.is_empty() {
+            bail!("round-rooms needs a map with rooms");
+        }
+        for room in map.rooms.to_vec() {
+            RoomCornerRounder::fill_if_corner(map, room.x1, room.y1)?;
+            RoomCornerRounder::fill_if_corner(map, room.x2 - 1, room.y1)?;
+            RoomCornerRounder::fill_if_corner(map, room.x1, room.y2 - 1)?;
+            RoomCornerRounder::fill_if_corner(map, room.x2 - 1, room.y2 - 1)?;
+        }
+        Ok(())
+    }
+}
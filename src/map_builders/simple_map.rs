@@ -0,0 +1,53 @@
+use bracket_lib::prelude::*;
+use glsp::GResult;
+
+use super::InitialMapBuilder;
+use crate::{map::Map, RNG};
+
+/// Random rooms, connected by L-shaped corridors
+pub struct SimpleMapBuilder {
+    pub max_rooms: i32,
+    pub min_size: i32,
+    pub max_size: i32,
+}
+
+impl InitialMapBuilder for SimpleMapBuilder {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        for _ in 0..self.max_rooms {
+            let (w, h) = {
+                let mut rng = RNG.lock().unwrap();
+                (
+                    rng.range(self.min_size, self.max_size),
+                    rng.range(self.min_size, self.max_size),
+                )
+            };
+            let (x, y) = {
+                let mut rng = RNG.lock().unwrap();
+                (
+                    rng.roll_dice(1, map.width - w - 1),
+                    rng.roll_dice(1, map.height - h - 1),
+                )
+            };
+            let new_room = Rect::with_size(x, y, w, h);
+            if map.rooms.iter().any(|other| new_room.intersect(other)) {
+                continue;
+            }
+
+            map.apply_room(&new_room)?;
+            if let Some(prev) = map.rooms.last() {
+                let new = new_room.center();
+                let prev = prev.center();
+                if RNG.lock().unwrap().range(0, 2) == 1 {
+                    map.apply_horizontal_tunnel(prev.x, new.x, prev.y)?;
+                    map.apply_vertical_tunnel(prev.y, new.y, new.x)?;
+                } else {
+                    map.apply_vertical_tunnel(prev.y, new.y, prev.x)?;
+                    map.apply_horizontal_tunnel(prev.x, new.x, new.y)?;
+                }
+            }
+            map.add_room(&new_room);
+            map.take_snapshot();
+        }
+        Ok(())
+    }
+}
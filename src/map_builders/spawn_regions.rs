@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use bracket_lib::prelude::*;
+use glsp::prelude::*;
+
+use super::MetaMapBuilder;
+use crate::{map::Map, RNG};
+
+enum RegionType {
+    /// One region per room
+    Rooms,
+    /// Regions of walkable tiles grouped by cellular noise
+    Areas,
+}
+
+/// Splits the map into regions where monsters and items are spawned.
+/// The region that contains the starting position is left empty
+pub struct SpawnRegions {
+    region_type: RegionType,
+}
+
+impl SpawnRegions {
+    pub fn new(region_type: Sym) -> GResult<Self> {
+        let region_type = match &*region_type.name() {
+            "rooms" => RegionType::Rooms,
+            "areas" => RegionType::Areas,
+            region_type => bail!("Unknown spawn region type: {}", region_type),
+        };
+        Ok(SpawnRegions { region_type })
+    }
+
+    fn room_regions(map: &Map) -> Vec<Vec<usize>> {
+        map.rooms
+            .iter()
+            .map(|room| {
+                let mut region = vec![];
+                for y in room.y1..room.y2 {
+                    for x in room.x1..room.x2 {
+                        let idx = map.xy_idx(x, y);
+                        if map.tiles[idx].walkable {
+                            region.push(idx);
+                        }
+                    }
+                }
+                region
+            })
+            .collect()
+    }
+
+    fn area_regions(map: &Map) -> Vec<Vec<usize>> {
+        let seed = RNG.lock().unwrap().roll_dice(1, 65536) as u64;
+        let mut noise = FastNoise::seeded(seed);
+        noise.set_noise_type(NoiseType::Cellular);
+        noise.set_frequency(0.08);
+        noise.set_cellular_distance_function(CellularDistanceFunction::Manhattan);
+
+        let mut areas: HashMap<i32, Vec<usize>> = HashMap::new();
+        for y in 1..map.height - 1 {
+            for x in 1..map.width - 1 {
+                let idx = map.xy_idx(x, y);
+                if map.tiles[idx].walkable {
+                    let cell = (noise.get_noise(x as f32, y as f32) * 10240.0) as i32;
+                    areas.entry(cell).or_insert_with(Vec::new).push(idx);
+                }
+            }
+        }
+
+        // Sort the areas to keep the generation deterministic for a given seed
+        let mut areas = areas.into_iter().collect::<Vec<_>>();
+        areas.sort_by_key(|(cell, _)| *cell);
+        areas.into_iter().map(|(_, region)| region).collect()
+    }
+}
+
+impl MetaMapBuilder for SpawnRegions {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        let regions = match self.region_type {
+            RegionType::Rooms => SpawnRegions::room_regions(map),
+            RegionType::Areas => SpawnRegions::area_regions(map),
+        };
+        let start_idx = map.start.map(|start| map.xy_idx(start.x, start.y));
+        map.spawn_regions = regions
+            .into_iter()
+            .filter(|region| !region.is_empty())
+            .filter(|region| match start_idx {
+                Some(start_idx) => !region.contains(&start_idx),
+                None => true,
+            })
+            .collect();
+        Ok(())
+    }
+}
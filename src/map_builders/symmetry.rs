@@ -0,0 +1,62 @@
+use glsp::prelude::*;
+
+use super::MetaMapBuilder;
+use crate::map::Map;
+
+enum Symmetry {
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+/// Mirrors the left half of the map on its right half (horizontal),
+/// and/or the top half on the bottom half (vertical)
+pub struct SymmetryBuilder {
+    mode: Symmetry,
+}
+
+impl SymmetryBuilder {
+    pub fn new(mode: Sym) -> GResult<Self> {
+        let mode = match &*mode.name() {
+            "horizontal" => Symmetry::Horizontal,
+            "vertical" => Symmetry::Vertical,
+            "both" => Symmetry::Both,
+            mode => bail!("Unknown symmetry mode: {}", mode),
+        };
+        Ok(SymmetryBuilder { mode })
+    }
+
+    fn mirror_horizontal(map: &mut Map) {
+        for y in 0..map.height {
+            for x in 0..map.width / 2 {
+                let src = map.xy_idx(x, y);
+                let dst = map.xy_idx(map.width - 1 - x, y);
+                map.tiles[dst] = map.tiles[src];
+            }
+        }
+    }
+
+    fn mirror_vertical(map: &mut Map) {
+        for y in 0..map.height / 2 {
+            for x in 0..map.width {
+                let src = map.xy_idx(x, y);
+                let dst = map.xy_idx(x, map.height - 1 - y);
+                map.tiles[dst] = map.tiles[src];
+            }
+        }
+    }
+}
+
+impl MetaMapBuilder for SymmetryBuilder {
+    fn build_map(&mut self, map: &mut Map) -> GResult<()> {
+        match self.mode {
+            Symmetry::Horizontal => SymmetryBuilder::mirror_horizontal(map),
+            Symmetry::Vertical => SymmetryBuilder::mirror_vertical(map),
+            Symmetry::Both => {
+                SymmetryBuilder::mirror_horizontal(map);
+                SymmetryBuilder::mirror_vertical(map);
+            }
+        }
+        Ok(())
+    }
+}
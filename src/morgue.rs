@@ -0,0 +1,65 @@
+use glsp::prelude::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::gamelog::GameLog;
+
+/// Directory the morgue files are written to
+#[cfg(not(target_arch = "wasm32"))]
+const MORGUE_DIR: &str = "morgue";
+
+pub fn bind_morgue() -> GResult<()> {
+    glsp::bind_rfn("morgue:write", &write_morgue)?;
+    Ok(())
+}
+
+/// Writes a morgue file, the plain text summary of a finished run.
+/// Returns its path, or nil if it could not be written: the error goes to the game log
+#[cfg(not(target_arch = "wasm32"))]
+fn write_morgue(lines: Vec<String>) -> Option<String> {
+    match create_morgue_file(&(lines.join("\n") + "\n")) {
+        Ok(path) => Some(path),
+        Err(err) => {
+            let message = format!("Could not write the morgue file: {}", err);
+            bracket_lib::prelude::console::log(&message);
+            GameLog::rglobal_add(&message);
+            None
+        }
+    }
+}
+
+/// Creates a new file named after the current time,
+/// with a counter when another run ended in the same second
+#[cfg(not(target_arch = "wasm32"))]
+fn create_morgue_file(contents: &str) -> std::io::Result<String> {
+    use std::{
+        fs::OpenOptions,
+        io::{ErrorKind, Write},
+        time::UNIX_EPOCH,
+    };
+
+    std::fs::create_dir_all(MORGUE_DIR)?;
+    let timestamp = UNIX_EPOCH.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+    let mut count = 1;
+    loop {
+        let path = match count {
+            1 => format!("{}/morgue-{}.txt", MORGUE_DIR, timestamp),
+            _ => format!("{}/morgue-{}-{}.txt", MORGUE_DIR, timestamp, count),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(contents.as_bytes())?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => count += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// There's no file system in the browser: the morgue is printed to the console,
+/// and nil is returned
+#[cfg(target_arch = "wasm32")]
+fn write_morgue(lines: Vec<String>) -> Option<String> {
+    bracket_lib::prelude::console::log(lines.join("\n"));
+    None
+}
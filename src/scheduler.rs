@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use glsp::prelude::*;
+
+use crate::ecs::Entity;
+
+/// Energy an actor needs to act. Actions cost energy,
+/// usually around this amount (see game/turns.glsp)
+pub const ACTION_THRESHOLD: i32 = 100;
+
+struct Actor {
+    energy: i32,
+    /// Energy gained per tick
+    speed: i32,
+}
+
+/// Energy based turn queue: each tick, the actors gain their speed in energy,
+/// and they can act once they have enough of it.
+/// Fast actors act more often than slow ones
+pub struct Scheduler {
+    actors: BTreeMap<Entity, Actor>,
+}
+
+impl RGlobal for Scheduler {}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            actors: BTreeMap::new(),
+        }
+    }
+
+    pub fn bind() -> GResult<()> {
+        glsp::add_rglobal(Scheduler::new());
+        glsp::bind_rfn("turn:add!", &|entity: Entity, speed: i32| {
+            Scheduler::borrow_mut().add(entity, speed)
+        })?;
+        glsp::bind_rfn("turn:remove!", &|entity: Entity| {
+            Scheduler::borrow_mut().actors.remove(&entity);
+        })?;
+        glsp::bind_rfn("turn:clear!", &|| Scheduler::borrow_mut().actors.clear())?;
+        glsp::bind_rfn("turn:set-speed!", &|entity: Entity, speed: i32| {
+            Scheduler::borrow_mut().actor_mut(entity)?.speed = speed;
+            Ok(())
+        })?;
+        glsp::bind_rfn("turn:speed", &|entity: Entity| {
+            Scheduler::borrow().actors.get(&entity).map(|a| a.speed)
+        })?;
+        glsp::bind_rfn("turn:energy", &|entity: Entity| {
+            Scheduler::borrow().actors.get(&entity).map(|a| a.energy)
+        })?;
+        glsp::bind_rfn("turn:spend!", &|entity: Entity, cost: i32| {
+            Scheduler::borrow_mut().actor_mut(entity)?.energy -= cost;
+            Ok(())
+        })?;
+        glsp::bind_rfn("turn:next", &|| Scheduler::borrow_mut().next())?;
+        Ok(())
+    }
+
+    /// New actors can act right away
+    pub fn add(&mut self, entity: Entity, speed: i32) {
+        self.actors.insert(
+            entity,
+            Actor {
+                energy: ACTION_THRESHOLD,
+                speed,
+            },
+        );
+    }
+
+    fn actor_mut(&mut self, entity: Entity) -> GResult<&mut Actor> {
+        match self.actors.get_mut(&entity) {
+            Some(actor) => Ok(actor),
+            None => bail!("Entity {} is not in the turn queue", entity),
+        }
+    }
+
+    /// Actors that have enough energy to act, the most energetic first
+    fn ready(&self) -> Vec<Entity> {
+        let mut ready = self
+            .actors
+            .iter()
+            .filter(|(_, actor)| actor.energy >= ACTION_THRESHOLD)
+            .map(|(&entity, actor)| (entity, actor.energy))
+            .collect::<Vec<_>>();
+        ready.sort_by(|a, b| b.1.cmp(&a.1));
+        ready.into_iter().map(|(entity, _)| entity).collect()
+    }
+
+    /// Advances the time until at least one actor can act, and returns the ready actors.
+    /// Returns an empty list if nobody will ever be able to act
+    pub fn next(&mut self) -> Vec<Entity> {
+        if !self.actors.values().any(|actor| actor.speed > 0) {
+            return self.ready();
+        }
+        loop {
+            let ready = self.ready();
+            if !ready.is_empty() {
+                return ready;
+            }
+            for actor in self.actors.values_mut() {
+                actor.energy += actor.speed;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_actors_are_ready() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add(1, 10);
+        scheduler.add(2, 10);
+        assert_eq!(scheduler.next(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_fast_actors_act_more() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add(1, 10);
+        scheduler.add(2, 20);
+        let mut turns = (0, 0);
+        for _ in 0..30 {
+            let entity = scheduler.next()[0];
+            if entity == 1 {
+                turns.0 += 1;
+            } else {
+                turns.1 += 1;
+            }
+            scheduler.actor_mut(entity).unwrap().energy -= ACTION_THRESHOLD;
+        }
+        assert_eq!(turns, (10, 20));
+    }
+
+    #[test]
+    fn test_most_energetic_first() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add(1, 10);
+        scheduler.add(2, 10);
+        scheduler.actor_mut(2).unwrap().energy += 50;
+        assert_eq!(scheduler.next(), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_nobody_can_act() {
+        let mut scheduler = Scheduler::new();
+        assert!(scheduler.next().is_empty());
+        scheduler.add(1, 0);
+        scheduler.actor_mut(1).unwrap().energy = 0;
+        assert!(scheduler.next().is_empty());
+    }
+}
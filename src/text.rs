@@ -0,0 +1,287 @@
+use bracket_lib::prelude::*;
+use glsp::prelude::*;
+
+use crate::utils::ss_idx;
+
+/// A run of text printed with the same color, or a tileset glyph
+#[derive(Debug, PartialEq, Clone)]
+pub struct Span {
+    /// A single space for the glyphs
+    pub text: String,
+    /// None for the default color of the print call
+    pub color: Option<RGB>,
+    pub glyph: Option<FontCharType>,
+}
+
+impl Span {
+    fn new(color: Option<RGB>) -> Self {
+        Span {
+            text: String::new(),
+            color,
+            glyph: None,
+        }
+    }
+
+    /// Width of the span on screen
+    pub fn width(&self) -> usize {
+        self.text.chars().count()
+    }
+}
+
+/// A markup tag
+enum Tag {
+    Color(Option<RGB>),
+    Glyph(FontCharType),
+}
+
+/// A char of a text, with its color and glyph
+type Cell = (char, Option<RGB>, Option<FontCharType>);
+
+pub fn bind_text() -> GResult<()> {
+    glsp::bind_rfn("text:strip", &|text: Val| strip_markup(&text.to_string()))?;
+    // lines of a text wrapped at a width, keeping its markup
+    glsp::bind_rfn("text:wrap", &|text: Val, width: usize| {
+        wrap_spans(&parse_markup(&text.to_string()), width)
+            .iter()
+            .map(|line| to_markup(line))
+            .collect::<Vec<_>>()
+    })?;
+    // case insensitive search, ignoring the markup
+    glsp::bind_rfn("text:contains?", &|text: Val, pattern: &str| {
+        strip_markup(&text.to_string())
+            .to_lowercase()
+            .contains(&pattern.to_lowercase())
+    })?;
+    Ok(())
+}
+
+/// Color of a markup tag: a name such as `red`, or a `#rrggbb` hex code
+fn tag_color(tag: &str) -> Option<RGB> {
+    if tag.starts_with('#') {
+        return RGB::from_hex(tag).ok();
+    }
+    let named = match tag {
+        "red" => (255, 80, 80),
+        "green" => (80, 255, 80),
+        "blue" => (100, 140, 255),
+        "yellow" => (255, 255, 0),
+        "orange" => (255, 165, 0),
+        "cyan" => (0, 255, 255),
+        "magenta" => (255, 0, 255),
+        "white" => (255, 255, 255),
+        "grey" => (128, 128, 128),
+        _ => return None,
+    };
+    Some(RGB::named(named))
+}
+
+/// Glyph of a `glyph:` tag: an index, or spritesheet coordinates as in tiles.glsp
+fn tag_glyph(tag: &str) -> Option<FontCharType> {
+    match tag.split_once(',') {
+        Some((x, y)) => Some(ss_idx(x.trim().parse().ok()?, y.trim().parse().ok()?)),
+        None => tag.trim().parse().ok(),
+    }
+}
+
+fn parse_tag(tag: &str) -> Option<Tag> {
+    if tag == "/" {
+        return Some(Tag::Color(None));
+    }
+    if let Some(glyph) = tag.strip_prefix("glyph:") {
+        return tag_glyph(glyph).map(Tag::Glyph);
+    }
+    tag_color(tag).map(|color| Tag::Color(Some(color)))
+}
+
+fn color_hex(c: RGB) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        (c.r * 255.0).round() as u8,
+        (c.g * 255.0).round() as u8,
+        (c.b * 255.0).round() as u8
+    )
+}
+
+/// Splits a text with inline markup into spans:
+/// `{red}Orc{/} hits you` colors "Orc" in red, `{/}` going back to the default color,
+/// and `{glyph:8,248}` or `{glyph:496}` draws a tileset glyph.
+/// Unknown tags are kept as text
+pub fn parse_markup(text: &str) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut current = Span::new(None);
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let tag = rest[start + 1..]
+            .find('}')
+            .map(|end| &rest[start + 1..start + 1 + end])
+            .and_then(|tag| parse_tag(tag).map(|parsed| (tag, parsed)));
+        match tag {
+            Some((tag, parsed)) => {
+                current.text.push_str(&rest[..start]);
+                let color = current.color;
+                if !current.text.is_empty() {
+                    spans.push(current);
+                }
+                current = match parsed {
+                    Tag::Color(color) => Span::new(color),
+                    Tag::Glyph(glyph) => {
+                        spans.push(Span {
+                            text: " ".to_string(),
+                            color,
+                            glyph: Some(glyph),
+                        });
+                        Span::new(color)
+                    }
+                };
+                rest = &rest[start + tag.len() + 2..];
+            }
+            None => {
+                current.text.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    current.text.push_str(rest);
+    if !current.text.is_empty() {
+        spans.push(current);
+    }
+    spans
+}
+
+/// Writes spans back as markup, their colors as hex codes
+pub fn to_markup(spans: &[Span]) -> String {
+    spans
+        .iter()
+        .map(|span| {
+            let text = match span.glyph {
+                Some(glyph) => format!("{{glyph:{}}}", glyph),
+                None => span.text.clone(),
+            };
+            match span.color {
+                Some(c) => format!("{{{}}}{}{{/}}", color_hex(c), text),
+                None => text,
+            }
+        })
+        .collect()
+}
+
+/// Splits spans into lines of at most `width` chars.
+/// Lines are broken between words and on newlines, the words longer than a line are cut
+pub fn wrap_spans(spans: &[Span], width: usize) -> Vec<Vec<Span>> {
+    let width = width.max(1);
+    let cells = spans
+        .iter()
+        .flat_map(|span| span.text.chars().map(move |c| (c, span.color, span.glyph)))
+        .collect::<Vec<Cell>>();
+
+    let mut lines = vec![];
+    for paragraph in cells.split(|&(c, _, glyph)| c == '\n' && glyph.is_none()) {
+        let mut line: Vec<Cell> = vec![];
+        for mut word in paragraph.split(|&(c, _, glyph)| c == ' ' && glyph.is_none()) {
+            loop {
+                let sep = usize::from(!line.is_empty());
+                if line.len() + sep + word.len() <= width {
+                    // the space takes the color of the text it follows
+                    if let Some(&(_, color, _)) = line.last() {
+                        line.push((' ', color, None));
+                    }
+                    line.extend_from_slice(word);
+                    break;
+                }
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                    continue;
+                }
+                let (head, tail) = word.split_at(width);
+                lines.push(head.to_vec());
+                word = tail;
+            }
+        }
+        lines.push(line);
+    }
+    lines.iter().map(|line| group_spans(line)).collect()
+}
+
+/// Merges consecutive chars of the same color into spans, each glyph being its own span
+fn group_spans(cells: &[Cell]) -> Vec<Span> {
+    let mut spans: Vec<Span> = vec![];
+    for &(c, color, glyph) in cells {
+        match spans.last_mut() {
+            Some(span) if glyph.is_none() && span.glyph.is_none() && span.color == color => {
+                span.text.push(c)
+            }
+            _ => spans.push(Span {
+                text: c.to_string(),
+                color,
+                glyph,
+            }),
+        }
+    }
+    spans
+}
+
+/// The text without its markup nor glyphs
+pub fn strip_markup(text: &str) -> String {
+    parse_markup(text)
+        .into_iter()
+        .filter(|span| span.glyph.is_none())
+        .map(|span| span.text)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text() {
+        let spans = parse_markup("You open the door");
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].text, "You open the door");
+        assert_eq!(spans[0].color, None);
+    }
+
+    #[test]
+    fn test_colored_spans() {
+        let spans = parse_markup("{red}Orc{/} hits {#00ff00}you");
+        let texts = spans.iter().map(|s| s.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["Orc", " hits ", "you"]);
+        assert_eq!(spans[0].color, tag_color("red"));
+        assert_eq!(spans[1].color, None);
+        assert_eq!(spans[2].color, Some(RGB::from_u8(0, 255, 0)));
+    }
+
+    #[test]
+    fn test_unknown_tags_are_text() {
+        assert_eq!(strip_markup("a {b} c { d"), "a {b} c { d");
+        assert_eq!(strip_markup("{yellow}Level up!{/}"), "Level up!");
+        assert_eq!(strip_markup("{glyph:x}"), "{glyph:x}");
+    }
+
+    #[test]
+    fn test_glyphs() {
+        let spans = parse_markup("{red}{glyph:8,248} Orc{/}{glyph:3}");
+        let glyphs = spans.iter().map(|s| s.glyph).collect::<Vec<_>>();
+        assert_eq!(glyphs, vec![Some(ss_idx(8, 248)), None, Some(3)]);
+        assert_eq!(spans[0].color, tag_color("red"));
+        assert_eq!(spans[0].width(), 1);
+        assert_eq!(strip_markup("{glyph:3} Orc"), " Orc");
+    }
+
+    #[test]
+    fn test_wrap() {
+        let wrap = |text: &str, width| {
+            wrap_spans(&parse_markup(text), width)
+                .iter()
+                .map(|line| line.iter().map(|s| s.text.as_str()).collect::<String>())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(wrap("The orc hits you", 8), vec!["The orc", "hits you"]);
+        assert_eq!(wrap("abcdefghij xy", 4), vec!["abcd", "efgh", "ij", "xy"]);
+        assert_eq!(wrap("a\n\nb", 10), vec!["a", "", "b"]);
+        // the markup doesn't take any room, a glyph takes one char
+        let lines = wrap_spans(&parse_markup("{red}big orc{/} hits {glyph:3}"), 7);
+        assert_eq!(to_markup(&lines[0]), "{#ff5050}big orc{/}");
+        assert_eq!(to_markup(&lines[1]), "hits {glyph:3}");
+    }
+}
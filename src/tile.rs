@@ -1,77 +1,357 @@
+use std::{collections::HashMap, sync::Mutex};
+
 use crate::{
     utils::{make_weighted_vec, ss_idx},
     RNG,
 };
 use bracket_lib::prelude::*;
+use glsp::prelude::*;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    pub static ref TILE_REGISTRY: Mutex<TileRegistry> = Mutex::new(TileRegistry::new());
+}
+
+/// Tile types used by the map builders.
+/// They must be defined in game/tiles.glsp
+pub const WALL: &str = "wall";
+pub const FLOOR: &str = "floor";
+pub const DOOR_CLOSED: &str = "door-closed";
+
+/// Index of a tile definition in the `TileRegistry`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TileType(pub usize);
+
+impl TileType {
+    /// Fails if the tile type is not registered
+    pub fn named(name: &str) -> GResult<Self> {
+        match TILE_REGISTRY.lock().unwrap().find(name) {
+            Some(tile_type) => Ok(tile_type),
+            None => bail!("Tile type \"{}\" is not registered", name),
+        }
+    }
+}
+
+/// Fog colors can be fixed, or computed from the lit color
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FogColor {
+    Fixed(RGB),
+    /// Greyscale version of the lit color, lerped towards black by this amount
+    Greyscale(f32),
+}
+
+impl FogColor {
+    fn apply(&self, lit: RGB) -> RGB {
+        match self {
+            FogColor::Fixed(color) => *color,
+            FogColor::Greyscale(amount) => lit.to_greyscale().lerp(RGB::named(BLACK), *amount),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TileDef {
+    pub name: String,
+    pub walkable: bool,
+    pub opaque: bool,
+    pub cost: f32,
+    /// Spritesheet indexes, with their weights
+    pub glyphs: Vec<(FontCharType, usize)>,
+    pub fg: RGB,
+    pub bg: RGB,
+    pub fg_fog: FogColor,
+    pub bg_fog: FogColor,
+    /// Random brightness multiplier applied to fg and bg
+    pub shade: Option<(f32, f32)>,
+    pub console: usize,
+    /// Name of the tile type that replaces this one when it's opened (e.g. doors)
+    pub opens_to: Option<String>,
+    /// Line glyphs indexed by the neighbours bitmask (see autotile.rs)
+    pub autotile: Option<Vec<FontCharType>>,
+}
+
+impl TileDef {
+    /// A walkable, transparent tile, with a single glyph
+    pub fn new(name: &str) -> Self {
+        TileDef {
+            name: name.to_string(),
+            walkable: true,
+            opaque: false,
+            cost: 1.0,
+            glyphs: vec![(32, 1)],
+            fg: RGB::named(WHITE),
+            bg: RGB::named(BLACK),
+            fg_fog: FogColor::Greyscale(0.5),
+            bg_fog: FogColor::Greyscale(0.5),
+            shade: None,
+            console: 0,
+            opens_to: None,
+            autotile: None,
+        }
+    }
+
+    /// Reads a definition from a glsp array:
+    /// `(name (walkable #t) (glyphs (x y weight) ...) ...)`
+    fn from_glsp(def: &Val) -> GResult<Self> {
+        let mut items = Vec::<Val>::from_val(def)?;
+        if items.is_empty() {
+            bail!("Empty tile definition");
+        }
+        let name = Sym::from_val(&items.remove(0))?;
+        let mut tile = TileDef::new(&name.name());
+
+        for item in items {
+            let mut prop = Vec::<Val>::from_val(&item)?;
+            if prop.len() < 2 {
+                bail!("Invalid property {} for tile {}", item, tile.name);
+            }
+            let key = Sym::from_val(&prop.remove(0))?;
+            let value = &prop[0];
+            match &*key.name() {
+                "walkable" => tile.walkable = bool::from_val(value)?,
+                "opaque" => tile.opaque = bool::from_val(value)?,
+                "cost" => tile.cost = Num::from_val(value)?.into_f32(),
+                "glyphs" => {
+                    tile.glyphs = vec![];
+                    for glyph in prop.iter() {
+                        let (x, y, weight) = match Vec::<i32>::from_val(glyph)?[..] {
+                            [x, y, weight] => (x, y, weight),
+                            _ => bail!(
+                                "Invalid glyph {} for tile {}, expected (x y weight)",
+                                glyph,
+                                tile.name
+                            ),
+                        };
+                        if weight <= 0 {
+                            bail!(
+                                "Invalid glyph {} for tile {}, the weight must be positive",
+                                glyph,
+                                tile.name
+                            );
+                        }
+                        tile.glyphs
+                            .push((sheet_glyph(glyph, x, y, &tile.name)?, weight as usize));
+                    }
+                }
+                "fg" => tile.fg = color(value)?,
+                "bg" => tile.bg = color(value)?,
+                "fg-fog" => tile.fg_fog = fog_color(value)?,
+                "bg-fog" => tile.bg_fog = fog_color(value)?,
+                "shade" => {
+                    tile.shade = Some((
+                        Num::from_val(&prop[0])?.into_f32(),
+                        Num::from_val(prop.get(1).unwrap_or(&prop[0]))?.into_f32(),
+                    ))
+                }
+                "console" => tile.console = usize::from_val(value)?,
+                "opens-to" => tile.opens_to = Some(Sym::from_val(value)?.name().to_string()),
+                "autotile" => {
+                    let mut glyphs = vec![];
+                    for glyph in prop.iter() {
+                        match Vec::<i32>::from_val(glyph)?[..] {
+                            [x, y] => glyphs.push(sheet_glyph(glyph, x, y, &tile.name)?),
+                            _ => bail!(
+                                "Invalid autotile glyph {} for tile {}, expected (x y)",
+                                glyph,
+                                tile.name
+                            ),
+                        }
+                    }
+                    if glyphs.len() != 16 {
+                        bail!(
+                            "Tile {} needs 16 autotile glyphs, found {}",
+                            tile.name,
+                            glyphs.len()
+                        );
+                    }
+                    tile.autotile = Some(glyphs);
+                }
+                key => bail!("Unknown property {} for tile {}", key, tile.name),
+            }
+        }
+        if tile.glyphs.is_empty() {
+            bail!("Tile {} needs at least one glyph", tile.name);
+        }
+        Ok(tile)
+    }
+}
+
+/// Spritesheet index of a glyph, from its coordinates
+fn sheet_glyph(glyph: &Val, x: i32, y: i32, tile: &str) -> GResult<FontCharType> {
+    if x < 0 || y < 0 || x > u16::MAX as i32 || y > u16::MAX as i32 {
+        bail!(
+            "Invalid glyph {} for tile {}, the coordinates are out of the spritesheet",
+            glyph,
+            tile
+        );
+    }
+    Ok(ss_idx(x as u16, y as u16))
+}
+
+/// A color is a hex string, or `(r g b)` floats which can go above 1
+/// to stay bright once shaded
+fn color(val: &Val) -> GResult<RGB> {
+    if let Val::Arr(_) = val {
+        return match Vec::<Num>::from_val(val)?[..] {
+            [r, g, b] => Ok(RGB::from_f32(r.into_f32(), g.into_f32(), b.into_f32())),
+            _ => bail!("Invalid color {}, expected (r g b)", val),
+        };
+    }
+    hex_color(val)
+}
+
+fn hex_color(val: &Val) -> GResult<RGB> {
+    let hex = String::from_val(val)?;
+    match RGB::from_hex(&hex) {
+        Ok(color) => Ok(color),
+        Err(_) => bail!("Invalid hex color: {}", hex),
+    }
+}
+
+/// A fog color is either a hex color, or a darkening amount for the greyscale version
+fn fog_color(val: &Val) -> GResult<FogColor> {
+    match val {
+        Val::Int(_) | Val::Flo(_) => Ok(FogColor::Greyscale(Num::from_val(val)?.into_f32())),
+        _ => Ok(FogColor::Fixed(hex_color(val)?)),
+    }
+}
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum TileType {
-    Floor,
-    Wall,
+/// All the tile types, loaded from game/tiles.glsp
+pub struct TileRegistry {
+    defs: Vec<TileDef>,
+    by_name: HashMap<String, TileType>,
 }
 
+impl TileRegistry {
+    pub fn new() -> Self {
+        TileRegistry {
+            defs: vec![],
+            by_name: HashMap::new(),
+        }
+    }
+
+    pub fn bind() -> GResult<()> {
+        glsp::bind_rfn("tiles:register", &|defs: Vec<Val>| -> GResult<()> {
+            let mut registry = TILE_REGISTRY.lock().unwrap();
+            for def in defs.iter() {
+                registry.register(TileDef::from_glsp(def)?);
+            }
+            for name in [WALL, FLOOR, DOOR_CLOSED] {
+                if registry.find(name).is_none() {
+                    bail!("Tile type {} is required by the map builders", name);
+                }
+            }
+            Ok(())
+        })?;
+        glsp::bind_rfn("tiles:names", &|| {
+            TILE_REGISTRY
+                .lock()
+                .unwrap()
+                .defs
+                .iter()
+                .map(|def| def.name.to_string())
+                .collect::<Vec<_>>()
+        })?;
+        Ok(())
+    }
+
+    /// Adds a tile definition, or replaces the one with the same name
+    pub fn register(&mut self, def: TileDef) -> TileType {
+        match self.by_name.get(&def.name) {
+            Some(&tile_type) => {
+                self.defs[tile_type.0] = def;
+                tile_type
+            }
+            None => {
+                let tile_type = TileType(self.defs.len());
+                self.by_name.insert(def.name.to_string(), tile_type);
+                self.defs.push(def);
+                tile_type
+            }
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<TileType> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn get(&self, tile_type: TileType) -> &TileDef {
+        &self.defs[tile_type.0]
+    }
+}
+
+/// A tile instance on the map.
+/// Its glyph and colors are picked from its definition when it's created
 #[derive(Clone, Copy, PartialEq)]
 pub struct Tile {
     pub tile_type: TileType,
+    pub walkable: bool,
+    pub opaque: bool,
+    pub cost: f32,
+    pub opens_to: Option<TileType>,
     pub glyph: FontCharType,
+    /// Line glyph, set by the autotiling pass
+    pub autotile: Option<FontCharType>,
     pub bg_fog: RGB,
     pub bg: RGB,
     pub fg_fog: RGB,
     pub fg: RGB,
     pub console: usize,
 }
+
 impl Tile {
-    pub fn wall() -> Self {
-        let weighted: Vec<u16> = make_weighted_vec(&[
-            (ss_idx(72, 216), 1),
-            (ss_idx(80, 216), 1),
-            (ss_idx(88, 216), 20),
-            (ss_idx(96, 216), 20),
-            (ss_idx(104, 216), 1),
-        ]);
+    pub fn new(tile_type: TileType) -> Self {
+        let registry = TILE_REGISTRY.lock().unwrap();
+        let def = registry.get(tile_type);
+        let weighted = make_weighted_vec(&def.glyphs);
         let mut rng = RNG.lock().unwrap();
-
-        let fg = RGB::named(ORANGE3);
-        let bg = RGB::from_hex("#c5b291").unwrap() * RGB::named(GREY20);
+        let glyph = *rng.random_slice_entry(&weighted).unwrap();
+        let (fg, bg) = match def.shade {
+            Some((min, max)) => {
+                let shade = if min < max { rng.range(min, max) } else { min };
+                (def.fg * shade, def.bg * shade)
+            }
+            None => (def.fg, def.bg),
+        };
+        let opens_to = def.opens_to.as_ref().and_then(|name| registry.find(name));
 
         Tile {
-            tile_type: TileType::Wall,
-            glyph: *rng.random_slice_entry(&weighted).unwrap(),
+            tile_type,
+            walkable: def.walkable,
+            opaque: def.opaque,
+            cost: def.cost,
+            opens_to,
+            glyph,
+            autotile: None,
             fg,
             bg,
-            fg_fog: fg.to_greyscale().lerp(RGB::named(BLACK), 0.5),
-            bg_fog: bg.to_greyscale(),
-            console: 0,
+            fg_fog: def.fg_fog.apply(fg),
+            bg_fog: def.bg_fog.apply(bg),
+            console: def.console,
         }
     }
 
-    pub fn floor() -> Self {
-        let choices = [
-            // small items
-            (ss_idx(40, 200), 1),
-            (ss_idx(112, 352), 1),
-            (ss_idx(120, 352), 1),
-            (ss_idx(112, 360), 1),
-            (ss_idx(120, 360), 1),
-            (32, 200),           // empty
-            (ss_idx(0, 384), 1), // dot
-            // decorations
-            (ss_idx(24, 144), 1), // skeleton
-        ];
-        let weighted = make_weighted_vec(&choices);
-        let mut rng = RNG.lock().unwrap();
-        let glyph = *rng.random_slice_entry(&weighted).unwrap();
-        let g = rng.range(0.5, 0.6);
-        let fg = RGB::from_f32(g * 1.2, g * 1.2, g * 1.2);
-        let bg = RGB::from_f32(g, g, g) * RGB::from_hex("#c5b291").unwrap();
-        Tile {
-            tile_type: TileType::Floor,
-            glyph,
-            fg,
-            bg,
-            fg_fog: RGB::named(BLACK),
-            bg_fog: bg.to_greyscale().lerp(RGB::named(BLACK), 0.5),
-            console: 0,
-        }
+    /// Fails if the tile type is not registered
+    pub fn named(name: &str) -> GResult<Self> {
+        Ok(Tile::new(TileType::named(name)?))
+    }
+
+    pub fn name(&self) -> String {
+        TILE_REGISTRY
+            .lock()
+            .unwrap()
+            .get(self.tile_type)
+            .name
+            .to_string()
+    }
+
+    /// Returns the line glyph matching a neighbours bitmask, if the tile type is autotiled
+    pub fn autotile_glyph(&self, mask: u8) -> Option<FontCharType> {
+        let registry = TILE_REGISTRY.lock().unwrap();
+        registry
+            .get(self.tile_type)
+            .autotile
+            .as_ref()
+            .map(|glyphs| glyphs[mask as usize])
     }
 }